@@ -0,0 +1,268 @@
+// Shared HTTP client for the Voice2Text service.
+// All requests go through one pooled reqwest::Client, built from the network
+// settings (proxy, extra CA certificates) and rebuilt when they change.
+// The streaming WebSocket connects separately but through the same proxy and roots.
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use serde::{Serialize, Deserialize};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+
+const DEFAULT_BASE_URL: &str = "https://voice2text.runitfast.xyz";
+const DEV_BASE_URL: &str = "http://localhost:3000";
+
+/// Overrides the service root from the settings, e.g. for a staging server.
+pub const BASE_URL_ENV: &str = "VOICE2TEXT_API_URL";
+
+// Per-endpoint request timeouts; connecting is bounded separately
+pub const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+pub const CAMPAIGNS_TIMEOUT: Duration = Duration::from_secs(10);
+pub const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(120);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Longest proxy reply to a CONNECT request that is accepted
+const MAX_PROXY_RESPONSE: usize = 8 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Service root; empty uses the built-in server (localhost in debug builds).
+    pub base_url: String,
+    /// Proxy for all requests, e.g. `http://proxy.corp:8080`. Empty uses the
+    /// system settings and the `HTTP_PROXY`/`HTTPS_PROXY` environment, except
+    /// for streaming, which only tunnels through a proxy set here.
+    pub proxy: String,
+    /// PEM files with extra root certificates, e.g. of a TLS-inspecting proxy.
+    pub ca_certs: Vec<String>,
+}
+
+struct Api {
+    base_url: String,
+    client: reqwest::Client,
+    /// Configured proxy, for WebSocket connections.
+    proxy: Option<reqwest::Url>,
+    /// Built-in plus extra root certificates, for WebSocket connections.
+    tls: Arc<rustls::ClientConfig>,
+}
+
+static API: RwLock<Option<Api>> = RwLock::new(None);
+
+fn build_api(config: &ApiConfig) -> Result<Api, String> {
+    let mut builder = reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT);
+    let mut roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+
+    let proxy = config.proxy.trim();
+    let proxy_url = if proxy.is_empty() {
+        None
+    } else {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?);
+        // reqwest accepts a bare host:port as an HTTP proxy
+        let with_scheme = if proxy.contains("://") { proxy.to_string() } else { format!("http://{}", proxy) };
+        Some(reqwest::Url::parse(&with_scheme).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?)
+    };
+
+    for path in config.ca_certs.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let pem = std::fs::read(path).map_err(|e| format!("Cannot read CA certificate {}: {}", path, e))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+        for der in CertificateDer::pem_slice_iter(&pem) {
+            let der = der.map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+            roots.add(der).map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+        }
+    }
+
+    let tls = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(Api {
+        base_url: resolve_base_url(config),
+        client: builder.build().map_err(|e| e.to_string())?,
+        proxy: proxy_url,
+        tls: Arc::new(tls),
+    })
+}
+
+fn resolve_base_url(config: &ApiConfig) -> String {
+    let configured = std::env::var(BASE_URL_ENV).ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| config.base_url.clone());
+    let configured = configured.trim().trim_end_matches('/');
+    if !configured.is_empty() {
+        return configured.to_string();
+    }
+    if cfg!(debug_assertions) { DEV_BASE_URL } else { DEFAULT_BASE_URL }.to_string()
+}
+
+/// Applies new network settings. Fails, keeping the current client, when the
+/// proxy or a certificate is unusable.
+pub fn configure(config: &ApiConfig) -> Result<(), String> {
+    let api = build_api(config)?;
+    *API.write().unwrap() = Some(api);
+    Ok(())
+}
+
+fn with_api<T>(f: impl FnOnce(&Api) -> T) -> T {
+    if let Some(api) = API.read().unwrap().as_ref() {
+        return f(api);
+    }
+    let mut api = API.write().unwrap();
+    let api = api.get_or_insert_with(|| build_api(&ApiConfig::default()).expect("default network settings are valid"));
+    f(api)
+}
+
+/// The shared client; cloning it shares the connection pool.
+pub fn client() -> reqwest::Client {
+    with_api(|api| api.client.clone())
+}
+
+pub fn base_url() -> String {
+    with_api(|api| api.base_url.clone())
+}
+
+/// Full URL of a service endpoint, e.g. `url("/api/transcribe")`.
+pub fn url(path: &str) -> String {
+    format!("{}{}", base_url(), path)
+}
+
+/// WebSocket URL of a service endpoint on the same host.
+pub fn ws_url(path: &str) -> String {
+    let base = base_url();
+    let base = match base.split_once("://") {
+        Some(("https", rest)) => format!("wss://{}", rest),
+        Some(("http", rest)) => format!("ws://{}", rest),
+        _ => base,
+    };
+    format!("{}{}", base, path)
+}
+
+/// POST to a service endpoint with that endpoint's timeout.
+pub fn post(path: &str, timeout: Duration) -> reqwest::RequestBuilder {
+    client().post(url(path)).timeout(timeout)
+}
+
+/// Opens a WebSocket like the shared client would: through the configured
+/// proxy, trusting the extra CA certificates.
+pub async fn connect_websocket(request: Request) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, String> {
+    let (proxy, tls) = with_api(|api| (api.proxy.clone(), api.tls.clone()));
+    let uri = request.uri();
+    let host = uri.host().ok_or("WebSocket URL has no host")?.to_string();
+    let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("wss") { 443 } else { 80 });
+
+    let connect = async {
+        match proxy {
+            Some(proxy) => tunnel(&proxy, &host, port).await,
+            None => TcpStream::connect((host.trim_matches(['[', ']']), port)).await.map_err(|e| e.to_string()),
+        }
+    };
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, connect).await.map_err(|_| "Connecting timed out".to_string())??;
+    let (socket, _) = tokio_tungstenite::client_async_tls_with_config(request, stream, None, Some(Connector::Rustls(tls)))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(socket)
+}
+
+/// Opens a connection to `host:port` tunnelled through an HTTP proxy with CONNECT.
+async fn tunnel(proxy: &reqwest::Url, host: &str, port: u16) -> Result<TcpStream, String> {
+    if proxy.scheme() != "http" {
+        return Err(format!("Streaming supports only http:// proxies, not {}://", proxy.scheme()));
+    }
+    let proxy_host = proxy.host_str().ok_or("Proxy has no host")?;
+    let proxy_port = proxy.port_or_known_default().unwrap_or(80);
+    let mut stream = TcpStream::connect((proxy_host.trim_matches(['[', ']']), proxy_port))
+        .await
+        .map_err(|e| format!("Cannot reach proxy {}: {}", proxy_host, e))?;
+
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+    if !proxy.username().is_empty() {
+        let credentials = format!("{}:{}", proxy.username(), proxy.password().unwrap_or(""));
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", BASE64_STANDARD.encode(credentials)));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    // Byte by byte, so nothing after the reply head is consumed
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_PROXY_RESPONSE {
+            return Err("Proxy sent an oversized reply".to_string());
+        }
+        head.push(stream.read_u8().await.map_err(|e| format!("Proxy closed the connection: {}", e))?);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(stream),
+        _ => Err(format!("Proxy refused the connection: {}", status_line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// A stand-in proxy that answers one CONNECT with `reply`, then sends `greeting`
+    /// down the tunnel. Yields the request head it received.
+    async fn stand_in_proxy(reply: &'static str, greeting: &'static [u8]) -> (reqwest::Url, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = reqwest::Url::parse(&format!("http://user:secret@{}", listener.local_addr().unwrap())).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut tcp, _) = listener.accept().await.unwrap();
+            let mut head = Vec::new();
+            while !head.ends_with(b"\r\n\r\n") {
+                head.push(tcp.read_u8().await.unwrap());
+            }
+            tcp.write_all(reply.as_bytes()).await.unwrap();
+            tcp.write_all(greeting).await.unwrap();
+            String::from_utf8(head).unwrap()
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn tunnels_through_the_proxy() {
+        let (proxy, server) = stand_in_proxy("HTTP/1.1 200 Connection established\r\n\r\n", b"hello").await;
+        let mut stream = tunnel(&proxy, "stream.example.com", 443).await.unwrap();
+
+        // Data after the reply head belongs to the tunnelled connection
+        let mut greeting = [0u8; 5];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(&greeting, b"hello");
+
+        let head = server.await.unwrap();
+        assert!(head.starts_with("CONNECT stream.example.com:443 HTTP/1.1\r\n"), "{head}");
+        assert!(head.contains("Host: stream.example.com:443\r\n"), "{head}");
+        assert!(head.contains(&format!("Proxy-Authorization: Basic {}\r\n", BASE64_STANDARD.encode("user:secret"))), "{head}");
+    }
+
+    #[tokio::test]
+    async fn refused_tunnel_is_an_error() {
+        let (proxy, _server) = stand_in_proxy("HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n", b"").await;
+        let err = tunnel(&proxy, "stream.example.com", 443).await.err().unwrap();
+        assert_eq!(err, "Proxy refused the connection: HTTP/1.1 407 Proxy Authentication Required");
+    }
+
+    #[tokio::test]
+    async fn only_http_proxies_can_tunnel() {
+        let proxy = reqwest::Url::parse("socks5://127.0.0.1:1080").unwrap();
+        let err = tunnel(&proxy, "stream.example.com", 443).await.err().unwrap();
+        assert!(err.contains("socks5"), "{err}");
+    }
+
+    #[test]
+    fn bare_proxy_address_is_http() {
+        let api = build_api(&ApiConfig { proxy: "proxy.corp:8080".into(), ..Default::default() }).unwrap();
+        let proxy = api.proxy.unwrap();
+        assert_eq!((proxy.scheme(), proxy.host_str(), proxy.port()), ("http", Some("proxy.corp"), Some(8080)));
+    }
+}
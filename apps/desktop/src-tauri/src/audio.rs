@@ -1,31 +1,307 @@
 #![allow(deprecated)]
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::{Arc, Mutex};
-use std::io::{Cursor, Write};
-use hound::{WavSpec, WavWriter};
+use cpal::{FromSample, Sample, SizedSample};
+use ringbuf::traits::{Consumer, Split};
+use ringbuf::{HeapCons, HeapRb};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::time::Duration;
+use hound::WavSpec;
+use serde::Serialize;
+use crate::error::AppError;
+use crate::spool::{SampleSpool, WavPayload, WavSink, SPILL_THRESHOLD};
+use crate::source::{AudioSource, SampleSink, SourceFormat, SourceStream, StreamFailure};
+use crate::vad::{self, VadConfig};
 
 #[allow(deprecated)]
-pub struct SendStream(#[allow(dead_code)] pub cpal::Stream);
+pub struct SendStream(pub cpal::Stream);
 unsafe impl Send for SendStream {}
 
+impl SourceStream for SendStream {
+    fn play(&mut self) -> Result<(), String> {
+        self.0.play().map_err(|e| e.to_string())
+    }
+}
+
+/// Builds the source for each stream the recorder opens, replacing the microphone.
+pub type SourceFactory = Box<dyn Fn() -> Result<Box<dyn AudioSource>, String> + Send>;
+
+/// Sample rate of every WAV handed to the transcription pipeline.
+pub const TARGET_SAMPLE_RATE: u32 = 16000;
+
+// Sinc kernel half-width in zero crossings and cutoff headroom for `Resampler`
+const RESAMPLE_ZERO_CROSSINGS: usize = 16;
+const RESAMPLE_ROLLOFF: f64 = 0.94;
+
+// Capture quality thresholds (linear full scale unless noted)
+const SILENCE_PEAK: f32 = 0.003; // ~ -50 dBFS
+const CLIP_LEVEL: f32 = 0.999;
+const CLIPPING_RATIO: f32 = 0.001;
+
+// Live level meter window and how many unsent readings may queue up
+const LEVEL_WINDOW_MS: usize = 50;
+const LEVEL_QUEUE: usize = 4;
+
+// Lock-free handoff between the audio callback and the capture worker
+const RING_SECONDS: usize = 2;
+const CALLBACK_SCRATCH: usize = 1024;
+const WORKER_POLL: Duration = Duration::from_millis(10);
+const WORKER_BLOCK: usize = 4096;
+
+const COMMON_SAMPLE_RATES: [u32; 11] = [8000, 11025, 16000, 22050, 32000, 44100, 48000, 88200, 96000, 176400, 192000];
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InputDeviceInfo {
+    /// Stable identifier used to select the device; survives renames and duplicate names.
+    pub id: String,
+    pub name: String,
+    pub host_api: String,
+    pub is_default: bool,
+    pub sample_rates: Vec<u32>,
+    pub channels: Vec<u16>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct AudioLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+/// Accumulates RMS/peak over captured samples and hands one reading per window to a channel.
+struct LevelMeter {
+    tx: SyncSender<AudioLevel>,
+    window: usize,
+    sum_sq: f32,
+    peak: f32,
+    count: usize,
+}
+
+impl LevelMeter {
+    fn push(&mut self, sample: f32) {
+        self.sum_sq += sample * sample;
+        self.peak = self.peak.max(sample.abs());
+        self.count += 1;
+        if self.count >= self.window {
+            let level = AudioLevel { rms: (self.sum_sq / self.count as f32).sqrt(), peak: self.peak };
+            // Never stall capture; drop readings if the UI falls behind
+            let _ = self.tx.try_send(level);
+            self.sum_sq = 0.0;
+            self.peak = 0.0;
+            self.count = 0;
+        }
+    }
+}
+
+/// A block of captured audio in the device's native format, for live streaming.
+pub struct CapturedFrames {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Interleaved samples; a block may end partway through a frame.
+    pub samples: Vec<f32>,
+}
+
+/// Copies everything recorded to a live consumer.
+struct FrameTap {
+    tx: Sender<CapturedFrames>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl FrameTap {
+    fn push(&self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        // A consumer that went away must not affect the recording
+        let _ = self.tx.send(CapturedFrames { sample_rate: self.sample_rate, channels: self.channels, samples: samples.to_vec() });
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureDiagnosis {
+    Ok,
+    /// Nothing above the noise of a muted or disconnected input.
+    Silent,
+    /// Signal present, but no frame stands out from the noise floor.
+    NoSpeech,
+    /// A noticeable share of samples hit full scale.
+    Clipping,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CaptureQuality {
+    pub peak: f32,
+    pub rms: f32,
+    pub noise_floor_db: f32,
+    pub clipped_ratio: f32,
+    pub speech_ratio: f32,
+    pub diagnosis: CaptureDiagnosis,
+}
+
+pub struct CapturedAudio {
+    pub wav: WavPayload,
+    pub duration: f32,
+    pub quality: CaptureQuality,
+}
+
+enum WorkerCommand {
+    SetPreroll(usize),
+    /// Acknowledged once handled, see `AudioRecorder::begin_capture`.
+    Start(Option<LevelMeter>, Option<FrameTap>, Sender<()>),
+    Stop(Sender<Result<SampleSpool, String>>),
+}
+
+/// Capture state owned by the worker thread. While not capturing, samples go
+/// to the pre-roll ring (if one is configured) instead of the recording spool.
+struct CaptureWorker {
+    channels: usize,
+    spool: Option<SampleSpool>,
+    preroll: VecDeque<f32>,
+    preroll_cap: usize,
+    meter: Option<LevelMeter>,
+    tap: Option<FrameTap>,
+    error: Option<String>,
+}
+
+impl CaptureWorker {
+    fn push(&mut self, samples: &[f32]) {
+        if let Some(spool) = self.spool.as_mut() {
+            if let Err(e) = spool.push_slice(samples) {
+                self.error.get_or_insert(e);
+            }
+            if let Some(meter) = self.meter.as_mut() {
+                for &s in samples { meter.push(s); }
+            }
+            if let Some(tap) = self.tap.as_ref() {
+                tap.push(samples);
+            }
+        } else if self.preroll_cap > 0 {
+            for &s in samples {
+                if self.preroll.len() >= self.preroll_cap {
+                    self.preroll.pop_front();
+                }
+                self.preroll.push_back(s);
+            }
+        }
+    }
+
+    fn handle(&mut self, command: WorkerCommand) {
+        match command {
+            WorkerCommand::SetPreroll(cap) => {
+                self.preroll_cap = cap;
+                while self.preroll.len() > cap {
+                    self.preroll.pop_front();
+                }
+            }
+            WorkerCommand::Start(meter, tap, ack) => {
+                let mut spool = SampleSpool::new(SPILL_THRESHOLD);
+                // Pre-roll holds whole frames only if it starts on a frame boundary
+                let skip = self.preroll.len() % self.channels;
+                let preroll: Vec<f32> = self.preroll.drain(..).skip(skip).collect();
+                self.error = spool.push_slice(&preroll).err();
+                self.spool = Some(spool);
+                self.meter = meter;
+                if let Some(tap) = tap.as_ref() {
+                    tap.push(&preroll);
+                }
+                self.tap = tap;
+                let _ = ack.send(());
+            }
+            WorkerCommand::Stop(reply) => {
+                // Dropping the meter and tap closes their channels
+                self.meter = None;
+                self.tap = None;
+                let spool = self.spool.take().unwrap_or_else(|| SampleSpool::new(SPILL_THRESHOLD));
+                let result = match self.error.take() {
+                    Some(e) => Err(format!("Failed to store recording: {}", e)),
+                    None => Ok(spool),
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    /// Drains the ring until the recorder hangs up, which happens when the stream is closed.
+    fn run(mut self, mut consumer: HeapCons<f32>, commands: Receiver<WorkerCommand>) {
+        let mut block = vec![0.0f32; WORKER_BLOCK];
+        loop {
+            let command = commands.recv_timeout(WORKER_POLL);
+            // Everything the callback produced so far belongs before the command
+            loop {
+                let n = consumer.pop_slice(&mut block);
+                if n == 0 { break; }
+                self.push(&block[..n]);
+            }
+            match command {
+                Ok(command) => self.handle(command),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+}
+
+/// An open source plus the worker thread that drains it.
+/// Field order matters: the source stops before the worker is told to exit.
+struct ActiveStream {
+    source: Box<dyn SourceStream>,
+    generation: u64,
+    commands: Sender<WorkerCommand>,
+    dropped: Arc<AtomicUsize>,
+}
+
 pub struct AudioRecorder {
-    pub buffer: Arc<Mutex<Vec<f32>>>,
-    stream: Option<SendStream>,
+    stream: Option<ActiveStream>,
     spec: WavSpec,
+    level_tx: Option<SyncSender<AudioLevel>>,
+    frame_tx: Option<Sender<CapturedFrames>>,
+    /// Device the warm (pre-roll) stream was opened on; `Some(None)` is the system default.
+    warm_device: Option<Option<String>>,
+    capturing: bool,
+    /// Audio captured on devices lost earlier in the current recording.
+    segments: Vec<Segment>,
+    errors: Option<Sender<StreamFailure>>,
+    generation: u64,
+    /// Replaces live capture, e.g. to replay a file; `None` uses the microphone.
+    source_factory: Option<SourceFactory>,
+}
+
+/// Samples captured on one device, in that device's native format.
+struct Segment {
+    spool: SampleSpool,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl Default for AudioRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AudioRecorder {
-    pub fn list_devices() -> Vec<String> {
+    pub fn list_devices() -> Vec<InputDeviceInfo> {
         let host = cpal::default_host();
-        let mut device_names = Vec::new();
-        if let Ok(devices) = host.input_devices() {
-            for device in devices {
-                if let Ok(name) = device.name() {
-                    device_names.push(name);
-                }
+        let default_id = host.default_input_device().and_then(|d| d.id().ok());
+        let mut devices = Vec::new();
+        if let Ok(inputs) = host.input_devices() {
+            for (index, device) in inputs.enumerate() {
+                let is_default = default_id.is_some() && device.id().ok() == default_id;
+                let (sample_rates, channels) = supported_formats(&device);
+                devices.push(InputDeviceInfo {
+                    id: device_id_string(&host, &device, index),
+                    name: device_display_name(&device, index),
+                    host_api: host.id().name().to_string(),
+                    is_default,
+                    sample_rates,
+                    channels,
+                });
             }
         }
-        device_names
+        devices
     }
 
     pub fn new() -> Self {
@@ -36,127 +312,901 @@ impl AudioRecorder {
             sample_format: hound::SampleFormat::Float,
         };
         Self {
-            buffer: Arc::new(Mutex::new(Vec::new())),
             stream: None,
             spec,
+            level_tx: None,
+            frame_tx: None,
+            warm_device: None,
+            capturing: false,
+            segments: Vec::new(),
+            errors: None,
+            generation: 0,
+            source_factory: None,
         }
     }
 
-    pub fn start(&mut self, device_name: Option<String>) -> Result<(), String> {
-        let host = cpal::default_host();
-        
-        let device = if let Some(target_name) = device_name {
-            let mut found = None;
-            if let Ok(devices) = host.input_devices() {
-                for d in devices {
-                    if let Ok(name) = d.name() {
-                        if name == target_name {
-                            found = Some(d);
-                            break;
-                        }
-                    }
-                }
-            }
-            found.ok_or(format!("Device '{}' not found", target_name))?
-        } else {
-             host.default_input_device()
-                .ok_or("No input device found")?
-        };
-        
-        let config = device.default_input_config().map_err(|e| e.to_string())?;
-        let sample_format = config.sample_format();
-        
-        self.spec.sample_rate = config.sample_rate();
-        self.spec.channels = config.channels();
-        
-        println!("INFO: Using Audio Device: {:?}", device.name().unwrap_or_default());
-        println!("INFO: Hardware Sample Format: {:?}", sample_format);
+    /// Routes backend errors of every stream opened from now on to `tx`.
+    pub fn set_error_sender(&mut self, tx: Sender<StreamFailure>) {
+        self.errors = Some(tx);
+    }
+
+    /// Captures from sources built by `factory` instead of the selected device
+    /// from now on; `None` switches back to the microphone.
+    pub fn set_source(&mut self, factory: Option<SourceFactory>) {
+        self.cool_down();
+        self.source_factory = factory;
+    }
+
+    /// Whether a failure with this generation belongs to the stream that is open now.
+    pub fn is_current_stream(&self, generation: u64) -> bool {
+        self.stream.as_ref().is_some_and(|s| s.generation == generation)
+    }
+
+    /// Whether the device behind `selector` (id or legacy name) is currently present.
+    pub fn device_available(selector: &str) -> bool {
+        find_input_device(&cpal::default_host(), selector).is_some()
+    }
+
+    /// Returns a receiver for ~50 ms level readings of the next recording.
+    /// The channel closes when that recording stops.
+    pub fn subscribe_levels(&mut self) -> Receiver<AudioLevel> {
+        let (tx, rx) = mpsc::sync_channel(LEVEL_QUEUE);
+        self.level_tx = Some(tx);
+        rx
+    }
+
+    /// Returns a receiver for all audio of the next recording as it is captured.
+    /// The channel closes when that recording stops.
+    pub fn subscribe_frames(&mut self) -> Receiver<CapturedFrames> {
+        let (tx, rx) = mpsc::channel();
+        self.frame_tx = Some(tx);
+        rx
+    }
+
+    pub fn is_warm(&self) -> bool {
+        self.warm_device.is_some()
+    }
+
+    /// Keeps the input stream open outside of recordings so the last
+    /// `preroll_ms` of audio can be prepended when the next recording starts.
+    pub fn warm_up(&mut self, device_name: Option<String>, preroll_ms: u32) -> Result<(), AppError> {
+        if self.capturing {
+            return Err(AppError::Other("Cannot change warm mic while recording".to_string()));
+        }
+        if self.source_factory.is_some() {
+            return Err(AppError::Other("Warm mic is unavailable while replaying a file".to_string()));
+        }
+        let reopen = self.warm_device.as_ref() != Some(&device_name);
+        if reopen {
+            self.cool_down();
+            let source = self.make_source(device_name.clone())?;
+            self.open_stream(source).map_err(AppError::Device)?;
+            self.warm_device = Some(device_name);
+        }
+        let cap = self.spec.sample_rate as usize * self.spec.channels as usize * preroll_ms as usize / 1000;
+        self.send(WorkerCommand::SetPreroll(cap)).map_err(AppError::Device)?;
+        if reopen {
+            self.play().map_err(AppError::Device)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the warm stream and discards any buffered pre-roll audio.
+    pub fn cool_down(&mut self) {
+        if self.warm_device.take().is_none() {
+            return;
+        }
+        let _ = self.send(WorkerCommand::SetPreroll(0));
+        if !self.capturing {
+            self.stream = None;
+        }
+    }
+
+    pub fn start(&mut self, device_name: Option<String>) -> Result<(), AppError> {
+        let reuse_warm = self.stream.is_some() && self.warm_device.as_ref() == Some(&device_name);
+        if !reuse_warm {
+            self.cool_down();
+            let source = self.make_source(device_name)?;
+            self.open_stream(source).map_err(AppError::Device)?;
+        }
+
+        self.segments.clear();
+        self.begin_capture().map_err(AppError::Device)?;
+        if !reuse_warm {
+            self.play().map_err(AppError::Device)?;
+        }
+        self.capturing = true;
+        Ok(())
+    }
+
+    /// Moves a running recording onto another device after its stream was lost.
+    /// Audio captured so far is kept and joined with the new device's audio on `stop`.
+    pub fn fail_over(&mut self, device_name: Option<String>) -> Result<(), AppError> {
+        if !self.capturing {
+            return Err(AppError::Other("Not recording".to_string()));
+        }
+        let spool = self.collect_spool().map_err(AppError::Device)?;
+        self.segments.push(Segment { spool, sample_rate: self.spec.sample_rate, channels: self.spec.channels });
+        self.warm_device = None;
+        self.stream = None;
+        let source = self.make_source(device_name)?;
+        self.open_stream(source).map_err(AppError::Device)?;
+        self.begin_capture().map_err(AppError::Device)?;
+        self.play().map_err(AppError::Device)
+    }
+
+    /// Ends the current recording and throws its audio away.
+    pub fn abort(&mut self) {
+        self.capturing = false;
+        self.level_tx = None;
+        self.frame_tx = None;
+        let _ = self.collect_spool();
+        self.segments.clear();
+        if self.warm_device.is_none() {
+            self.stream = None;
+        }
+    }
+
+    /// Switches the worker from pre-roll to recording and waits until it has.
+    /// A stream played before that could have its first blocks taken for pre-roll.
+    fn begin_capture(&self) -> Result<(), String> {
+        let (ack_tx, ack_rx) = mpsc::channel();
+        self.send(WorkerCommand::Start(self.level_meter(), self.frame_tap(), ack_tx))?;
+        ack_rx.recv().map_err(|_| "Capture worker stopped".to_string())
+    }
+
+    fn frame_tap(&self) -> Option<FrameTap> {
+        self.frame_tx.clone().map(|tx| FrameTap { tx, sample_rate: self.spec.sample_rate, channels: self.spec.channels })
+    }
+
+    fn level_meter(&self) -> Option<LevelMeter> {
+        let window = self.spec.sample_rate as usize * self.spec.channels as usize * LEVEL_WINDOW_MS / 1000;
+        self.level_tx.clone().map(|tx| LevelMeter { tx, window: window.max(1), sum_sq: 0.0, peak: 0.0, count: 0 })
+    }
+
+    /// Ends capture on the worker and takes the recorded samples.
+    fn collect_spool(&self) -> Result<SampleSpool, String> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+        match self.send(WorkerCommand::Stop(reply_tx)) {
+            Ok(()) => reply_rx.recv().map_err(|_| "Capture worker stopped".to_string())?,
+            Err(_) => Ok(SampleSpool::new(SPILL_THRESHOLD)),
+        }
+    }
+
+    fn send(&self, command: WorkerCommand) -> Result<(), String> {
+        let active = self.stream.as_ref().ok_or("No active audio stream")?;
+        active.commands.send(command).map_err(|_| "Capture worker stopped".to_string())
+    }
+
+    fn make_source(&self, device_name: Option<String>) -> Result<Box<dyn AudioSource>, AppError> {
+        match &self.source_factory {
+            // Replayed files fail to open like any other file
+            Some(factory) => factory().map_err(AppError::Io),
+            None => Ok(Box::new(CpalSource::new(device_name)?)),
+        }
+    }
+
+    fn play(&mut self) -> Result<(), String> {
+        self.stream.as_mut().ok_or("No active audio stream")?.source.play()
+    }
+
+    /// Opens `source` paused, with a fresh ring and capture worker behind it.
+    fn open_stream(&mut self, source: Box<dyn AudioSource>) -> Result<(), String> {
+        let SourceFormat { sample_rate, channels } = source.format();
+        self.spec.sample_rate = sample_rate;
+        self.spec.channels = channels;
+
+        println!("INFO: Using Audio Source: {}", source.name());
         println!("INFO: Config: {}Hz, {} channels", self.spec.sample_rate, self.spec.channels);
 
-        let buffer = self.buffer.clone();
-        
-        let stream = match sample_format {
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &config.into(),
-                move |data: &[f32], _| {
-                    if let Ok(mut b) = buffer.lock() { b.extend_from_slice(data); }
-                },
-                |err| eprintln!("Audio error: {}", err),
-                None
-            ),
-            cpal::SampleFormat::I16 => device.build_input_stream(
-                &config.into(),
-                move |data: &[i16], _| {
-                    if let Ok(mut b) = buffer.lock() {
-                        // Convert i16 to f32 [-1.0, 1.0]
-                        b.extend(data.iter().map(|&s| s as f32 / i16::MAX as f32));
-                    }
-                },
-                |err| eprintln!("Audio error: {}", err),
-                None
-            ),
-            _ => return Err(format!("Unsupported sample format: {:?}", sample_format)),
-        }.map_err(|e| e.to_string())?;
+        let ring_len = self.spec.sample_rate as usize * self.spec.channels as usize * RING_SECONDS;
+        let (producer, consumer) = HeapRb::<f32>::new(ring_len.max(CALLBACK_SCRATCH)).split();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        self.generation += 1;
+        let sink = SampleSink::new(producer, dropped.clone(), self.errors.clone(), self.generation);
+        let stream = source.open(sink)?;
+
+        let (commands, command_rx) = mpsc::channel();
+        let worker = CaptureWorker {
+            channels: self.spec.channels.max(1) as usize,
+            spool: None,
+            preroll: VecDeque::new(),
+            preroll_cap: 0,
+            meter: None,
+            tap: None,
+            error: None,
+        };
+        std::thread::spawn(move || worker.run(consumer, command_rx));
 
-        stream.play().map_err(|e| e.to_string())?;
-        self.stream = Some(SendStream(stream));
+        self.stream = Some(ActiveStream { source: stream, generation: self.generation, commands, dropped });
         Ok(())
     }
 
-    pub fn stop(&mut self) -> Result<(Vec<u8>, f32), String> {
-        let _ = self.stream.take();
-        let buffer_guard = self.buffer.lock().unwrap();
-        
-        // Check for signal
-        let mut max_amp = 0.0f32;
-        for &s in buffer_guard.iter() {
-            if s.abs() > max_amp { max_amp = s.abs(); }
+    pub fn stop(&mut self) -> Result<CapturedAudio, AppError> {
+        self.capturing = false;
+        self.level_tx = None;
+        self.frame_tx = None;
+
+        let mut segments = std::mem::take(&mut self.segments);
+        // Sources not paced in real time hand over the rest of their input first
+        if let Some(active) = self.stream.as_mut() {
+            active.source.finish();
+        }
+        let spool = self.collect_spool().map_err(AppError::Device)?;
+        segments.push(Segment { spool, sample_rate: self.spec.sample_rate, channels: self.spec.channels });
+
+        if let Some(active) = self.stream.as_ref() {
+            let dropped = active.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                println!("WARN: Capture ring overflowed, {} samples dropped", dropped);
+            }
+        }
+        // A warm stream stays open to keep filling the pre-roll
+        if self.warm_device.is_none() {
+            self.stream = None;
         }
 
         // Force Output Spec: 16kHz Mono 16-bit Int (Standard for ASR)
         let out_spec = WavSpec {
             channels: 1,
-            sample_rate: self.spec.sample_rate, // Keep native rate to avoid aliasing issues for now
+            sample_rate: TARGET_SAMPLE_RATE,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
 
-        let mut wav_data = Vec::new();
-        let mut cursor = Cursor::new(&mut wav_data);
-        let mut writer = WavWriter::new(&mut cursor, out_spec).map_err(|e| e.to_string())?;
-        
-        // Downmix to Mono & Convert to i16
-        // If we have 2 channels, we average them. If 1, we just take it.
-        let channels = self.spec.channels as usize;
-        
-        for chunk in buffer_guard.chunks(channels) {
-            let mut sum = 0.0;
-            for &sample in chunk {
-                sum += sample;
-            }
-            let avg = sum / channels as f32;
-            
-            // Hard clip and scale to i16 range
-            let sample_i16 = (avg.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-            writer.write_sample(sample_i16).map_err(|e| e.to_string())?;
-        }
-        writer.finalize().map_err(|e| e.to_string())?;
-        
+        // Downmix to Mono, then band-limit and resample to the ASR rate.
+        // Long recordings are processed block by block from the spill file.
+        // After a device failover each segment is converted from its own format.
+        let on_disk = segments.iter().any(|seg| seg.spool.is_spilled());
+        let mut sink = WavSink::new(out_spec, on_disk).map_err(AppError::Io)?;
+        // Judged on the 16 kHz output, so every segment is framed alike and VAD sees the same audio
+        let mut analyzer = QualityAnalyzer::new(TARGET_SAMPLE_RATE);
+        let mut duration_sec = 0.0f32;
+
+        for segment in segments {
+            let channels = segment.channels.max(1) as usize;
+            let mut resampler = Resampler::new(segment.sample_rate, TARGET_SAMPLE_RATE);
+            let mut carry: Vec<f32> = Vec::new();
+            let mut mono = Vec::new();
+            let mut resampled = Vec::new();
+            let mut mono_len = 0usize;
+
+            segment.spool.for_each_block(|block| {
+                carry.extend_from_slice(block);
+                let whole = carry.len() - carry.len() % channels;
+                mono.clear();
+                mono.extend(carry[..whole].chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32));
+                carry.drain(..whole);
+
+                mono_len += mono.len();
+                resampled.clear();
+                resampler.process(&mono, &mut resampled);
+                for &s in &resampled { analyzer.push(s); }
+                write_i16(&mut sink, &resampled)
+            }).map_err(AppError::Io)?;
+            resampled.clear();
+            resampler.finish(&mut resampled);
+            for &s in &resampled { analyzer.push(s); }
+            write_i16(&mut sink, &resampled).map_err(AppError::Io)?;
+            duration_sec += mono_len as f32 / segment.sample_rate as f32;
+        }
+
+        let quality = analyzer.finish();
+        let wav = sink.finish().map_err(AppError::Io)?;
+
         if let Ok(appdata) = std::env::var("APPDATA") {
             let log_dir = std::path::Path::new(&appdata).join("Voice2Text").join("logs");
             let _ = std::fs::create_dir_all(&log_dir);
             let debug_file = log_dir.join("debug_recording.wav");
-            if let Ok(mut file) = std::fs::File::create(&debug_file) {
-                let _ = file.write_all(&wav_data);
+            let _ = wav.write_to(&debug_file);
+        }
+
+        Ok(CapturedAudio { wav, duration: duration_sec, quality })
+    }
+}
+
+/// Live capture from a cpal input device.
+pub struct CpalSource {
+    device: cpal::Device,
+    config: cpal::SupportedStreamConfig,
+}
+
+impl CpalSource {
+    /// Resolves `device_name` (id or legacy name), or the system default for `None`.
+    pub fn new(device_name: Option<String>) -> Result<Self, AppError> {
+        let host = cpal::default_host();
+
+        let device = if let Some(selector) = device_name {
+            find_input_device(&host, &selector).ok_or_else(|| AppError::DeviceNotFound(format!("Device '{}' not found", selector)))?
+        } else {
+             host.default_input_device()
+                .ok_or_else(|| AppError::DeviceNotFound("No input device found".to_string()))?
+        };
+
+        let config = device.default_input_config().map_err(|e| AppError::Device(e.to_string()))?;
+        println!("INFO: Hardware Sample Format: {:?}", config.sample_format());
+        Ok(Self { device, config })
+    }
+}
+
+impl AudioSource for CpalSource {
+    fn name(&self) -> String {
+        device_display_name(&self.device, 0)
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat { sample_rate: self.config.sample_rate(), channels: self.config.channels() }
+    }
+
+    fn open(self: Box<Self>, sink: SampleSink) -> Result<Box<dyn SourceStream>, String> {
+        let device = &self.device;
+        let sample_format = self.config.sample_format();
+        let stream_config: cpal::StreamConfig = self.config.clone().into();
+
+        let stream = match sample_format {
+            cpal::SampleFormat::I8 => build_input_stream::<i8>(device, &stream_config, sink),
+            cpal::SampleFormat::I16 => build_input_stream::<i16>(device, &stream_config, sink),
+            cpal::SampleFormat::I24 => build_input_stream::<cpal::I24>(device, &stream_config, sink),
+            cpal::SampleFormat::I32 => build_input_stream::<i32>(device, &stream_config, sink),
+            cpal::SampleFormat::I64 => build_input_stream::<i64>(device, &stream_config, sink),
+            cpal::SampleFormat::U8 => build_input_stream::<u8>(device, &stream_config, sink),
+            cpal::SampleFormat::U16 => build_input_stream::<u16>(device, &stream_config, sink),
+            cpal::SampleFormat::U24 => build_input_stream::<cpal::U24>(device, &stream_config, sink),
+            cpal::SampleFormat::U32 => build_input_stream::<u32>(device, &stream_config, sink),
+            cpal::SampleFormat::U64 => build_input_stream::<u64>(device, &stream_config, sink),
+            cpal::SampleFormat::F32 => build_input_stream::<f32>(device, &stream_config, sink),
+            cpal::SampleFormat::F64 => build_input_stream::<f64>(device, &stream_config, sink),
+            // DSD bitstreams are not PCM and can't be converted sample by sample
+            _ => return Err(format!("Unsupported sample format: {:?}", sample_format)),
+        }?;
+        Ok(Box::new(SendStream(stream)))
+    }
+}
+
+/// Stable id for a device: cpal's persistent id where the backend has one,
+/// otherwise host, name and enumeration index.
+fn device_id_string(host: &cpal::Host, device: &cpal::Device, index: usize) -> String {
+    match device.id() {
+        Ok(id) => id.to_string(),
+        Err(_) => format!("{}:{}#{}", host.id().name(), device_display_name(device, index), index),
+    }
+}
+
+fn device_display_name(device: &cpal::Device, index: usize) -> String {
+    device.description()
+        .map(|d| d.name().to_string())
+        .unwrap_or_else(|_| format!("Input {}", index + 1))
+}
+
+/// Resolves a device by id, falling back to an exact name match for
+/// selections saved before devices were identified by id.
+fn find_input_device(host: &cpal::Host, selector: &str) -> Option<cpal::Device> {
+    if let Ok(id) = selector.parse::<cpal::DeviceId>() {
+        if let Some(device) = host.device_by_id(&id) {
+            return Some(device);
+        }
+    }
+    let devices: Vec<cpal::Device> = host.input_devices().ok()?.collect();
+    if let Some(i) = devices.iter().enumerate().position(|(i, d)| device_id_string(host, d, i) == selector) {
+        return devices.into_iter().nth(i);
+    }
+    devices.into_iter().enumerate()
+        .find(|(i, d)| device_display_name(d, *i) == selector)
+        .map(|(_, d)| d)
+}
+
+/// Common rates inside the supported ranges (plus the range ends) and the channel counts on offer.
+fn supported_formats(device: &cpal::Device) -> (Vec<u32>, Vec<u16>) {
+    let mut rates = Vec::new();
+    let mut channels = Vec::new();
+    if let Ok(configs) = device.supported_input_configs() {
+        for range in configs {
+            let (min, max) = (range.min_sample_rate(), range.max_sample_rate());
+            rates.push(min);
+            rates.push(max);
+            rates.extend(COMMON_SAMPLE_RATES.iter().copied().filter(|r| (min..=max).contains(r)));
+            channels.push(range.channels());
+        }
+    }
+    rates.sort_unstable();
+    rates.dedup();
+    channels.sort_unstable();
+    channels.dedup();
+    (rates, channels)
+}
+
+/// Opens an input stream for hardware sample type `T`. The callback only
+/// converts to f32 and pushes into the lock-free ring; samples that don't
+/// fit are counted as dropped rather than blocking the audio thread.
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut sink: SampleSink,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let failures = sink.failures();
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            let mut scratch = [0.0f32; CALLBACK_SCRATCH];
+            for chunk in data.chunks(CALLBACK_SCRATCH) {
+                for (dst, &s) in scratch.iter_mut().zip(chunk) {
+                    *dst = sample_to_f32(s);
+                }
+                sink.push(&scratch[..chunk.len()]);
+            }
+        },
+        move |err| {
+            // Only a vanished device or invalidated stream is lost; underruns and
+            // backend specific errors are glitches the stream recovers from
+            let fatal = matches!(err, cpal::StreamError::DeviceNotAvailable | cpal::StreamError::StreamInvalidated);
+            failures.report(err.to_string(), fatal);
+        },
+        None
+    ).map_err(|e| e.to_string())
+}
+
+fn write_i16(sink: &mut WavSink, samples: &[f32]) -> Result<(), String> {
+    for &s in samples {
+        sink.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    Ok(())
+}
+
+/// Converts one hardware sample to f32 in [-1.0, 1.0].
+///
+/// Signed formats are scaled by their full range (`i16::MIN` maps to -1.0),
+/// unsigned formats are re-centred on their midpoint first (`128u8` is 0.0).
+pub fn sample_to_f32<T>(sample: T) -> f32
+where
+    T: Sample,
+    f32: FromSample<T>,
+{
+    f32::from_sample_(sample)
+}
+
+/// Level statistics of a mono recording, accumulated sample by sample.
+pub struct QualityAnalyzer {
+    frame_len: usize,
+    frame_sum_sq: f32,
+    frame_count: usize,
+    levels: Vec<f32>,
+    peak: f32,
+    sum_sq: f64,
+    clipped: usize,
+    count: usize,
+}
+
+impl QualityAnalyzer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            frame_len: (sample_rate as usize * VadConfig::default().frame_ms as usize / 1000).max(1),
+            frame_sum_sq: 0.0,
+            frame_count: 0,
+            levels: Vec::new(),
+            peak: 0.0,
+            sum_sq: 0.0,
+            clipped: 0,
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let abs = sample.abs();
+        self.peak = self.peak.max(abs);
+        self.sum_sq += (sample * sample) as f64;
+        self.count += 1;
+        if abs >= CLIP_LEVEL {
+            self.clipped += 1;
+        }
+
+        self.frame_sum_sq += sample * sample;
+        self.frame_count += 1;
+        if self.frame_count == self.frame_len {
+            self.close_frame();
+        }
+    }
+
+    fn close_frame(&mut self) {
+        self.levels.push(level_db((self.frame_sum_sq / self.frame_count as f32).sqrt()));
+        self.frame_sum_sq = 0.0;
+        self.frame_count = 0;
+    }
+
+    pub fn finish(mut self) -> CaptureQuality {
+        if self.count == 0 {
+            return CaptureQuality {
+                peak: 0.0,
+                rms: 0.0,
+                noise_floor_db: level_db(0.0),
+                clipped_ratio: 0.0,
+                speech_ratio: 0.0,
+                diagnosis: CaptureDiagnosis::Silent,
+            };
+        }
+        if self.frame_count > 0 {
+            self.close_frame();
+        }
+
+        let peak = self.peak;
+        let rms = (self.sum_sq / self.count as f64).sqrt() as f32;
+        let clipped_ratio = self.clipped as f32 / self.count as f32;
+
+        // Same speech test as VAD with its default settings
+        let levels = self.levels;
+        let noise_floor_db = vad::noise_floor_db(&levels);
+        let threshold = vad::speech_threshold_db(noise_floor_db, &VadConfig::default());
+        let speech_ratio = levels.iter().filter(|&&l| l >= threshold).count() as f32 / levels.len() as f32;
+
+        let diagnosis = if peak < SILENCE_PEAK {
+            CaptureDiagnosis::Silent
+        } else if clipped_ratio > CLIPPING_RATIO {
+            CaptureDiagnosis::Clipping
+        } else if speech_ratio == 0.0 {
+            CaptureDiagnosis::NoSpeech
+        } else {
+            CaptureDiagnosis::Ok
+        };
+
+        CaptureQuality { peak, rms, noise_floor_db, clipped_ratio, speech_ratio, diagnosis }
+    }
+}
+
+fn level_db(linear: f32) -> f32 {
+    20.0 * linear.max(1e-6).log10()
+}
+
+/// Band-limited sample rate converter using a Blackman-windowed sinc kernel.
+///
+/// When downsampling, the kernel cutoff sits just below the output Nyquist
+/// frequency so content above it is filtered out instead of folding back
+/// into the speech band. Input can be fed in blocks of any size; only the
+/// samples still under the kernel are kept between calls.
+pub struct Resampler {
+    passthrough: bool,
+    step: f64,
+    cutoff: f64,
+    half_width: i64,
+    history: Vec<f32>,
+    /// Absolute input index of `history[0]`.
+    base: i64,
+    total_in: i64,
+    next_out: u64,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let passthrough = from_rate == to_rate || from_rate == 0 || to_rate == 0;
+        let step = from_rate as f64 / to_rate.max(1) as f64;
+        // Normalized cutoff (1.0 = input Nyquist), with a little headroom for the transition band
+        let cutoff = (to_rate as f64 / from_rate.max(1) as f64).min(1.0) * RESAMPLE_ROLLOFF;
+        let half_width = (RESAMPLE_ZERO_CROSSINGS as f64 / cutoff).ceil() as i64;
+        Self { passthrough, step, cutoff, half_width, history: Vec::new(), base: 0, total_in: 0, next_out: 0 }
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        if self.passthrough {
+            output.extend_from_slice(input);
+            return;
+        }
+        self.history.extend_from_slice(input);
+        self.total_in += input.len() as i64;
+
+        // Emit every output whose kernel is fully covered by the input seen so far
+        loop {
+            let center = self.next_out as f64 * self.step;
+            if center.floor() as i64 + self.half_width > self.total_in - 1 {
+                break;
             }
+            output.push(self.sample_at(center));
+            self.next_out += 1;
+        }
+
+        let center = self.next_out as f64 * self.step;
+        let keep_from = center.floor() as i64 - self.half_width + 1;
+        if keep_from > self.base {
+            let drop = ((keep_from - self.base) as usize).min(self.history.len());
+            self.history.drain(..drop);
+            self.base += drop as i64;
+        }
+    }
+
+    /// Emits the tail, treating samples past the end of input as absent.
+    pub fn finish(&mut self, output: &mut Vec<f32>) {
+        if self.passthrough {
+            return;
+        }
+        let out_len = (self.total_in as f64 / self.step).floor() as u64;
+        while self.next_out < out_len {
+            let center = self.next_out as f64 * self.step;
+            output.push(self.sample_at(center));
+            self.next_out += 1;
+        }
+    }
+
+    fn sample_at(&self, center: f64) -> f32 {
+        let first = (center.floor() as i64 - self.half_width + 1).max(0);
+        let end = (center.floor() as i64 + self.half_width).min(self.total_in - 1);
+
+        let mut acc = 0.0f64;
+        let mut weight_sum = 0.0f64;
+        for k in first..=end {
+            let x = center - k as f64;
+            let w = self.cutoff * sinc(self.cutoff * x) * blackman(x / self.half_width as f64);
+            acc += self.history[(k - self.base) as usize] as f64 * w;
+            weight_sum += w;
+        }
+        // Normalize so DC gain stays at unity, including near the edges
+        let sample = if weight_sum.abs() > f64::EPSILON { acc / weight_sum } else { 0.0 };
+        sample as f32
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let px = std::f64::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Blackman window over `x` in [-1, 1]; zero outside.
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    let t = std::f64::consts::PI * (x + 1.0);
+    0.42 - 0.5 * t.cos() + 0.08 * (2.0 * t).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::SyntheticSource;
+    use crate::transcribe::{self, BackendFuture, FullResponse, TranscriptionBackend};
+    use std::sync::Mutex;
+
+    const SWEEP_SECS: f64 = 2.0;
+    const SWEEP_AMPLITUDE: f32 = 0.5;
+
+    /// Linear sine sweep from `f0` to `f1` Hz.
+    fn sweep(rate: u32, f0: f64, f1: f64) -> Vec<f32> {
+        let len = (rate as f64 * SWEEP_SECS) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / rate as f64;
+                let phase = 2.0 * std::f64::consts::PI * (f0 * t + (f1 - f0) * t * t / (2.0 * SWEEP_SECS));
+                SWEEP_AMPLITUDE * phase.sin() as f32
+            })
+            .collect()
+    }
+
+    /// Resamples to 16 kHz in uneven blocks, the way `stop` feeds the spool.
+    fn resample(input: &[f32], rate: u32) -> Vec<f32> {
+        let mut resampler = Resampler::new(rate, TARGET_SAMPLE_RATE);
+        let mut output = Vec::new();
+        for block in input.chunks(1237) {
+            resampler.process(block, &mut output);
+        }
+        resampler.finish(&mut output);
+        output
+    }
+
+    /// RMS without the first and last 50 ms, where the kernel runs off the input.
+    fn inner_rms(samples: &[f32]) -> f32 {
+        let edge = TARGET_SAMPLE_RATE as usize / 20;
+        let inner = &samples[edge..samples.len() - edge];
+        (inner.iter().map(|s| s * s).sum::<f32>() / inner.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn resampled_length_follows_rate_ratio() {
+        for rate in [44100, 48000, 96000] {
+            let input = sweep(rate, 100.0, 7000.0);
+            let expected = input.len() as f64 * TARGET_SAMPLE_RATE as f64 / rate as f64;
+            let output = resample(&input, rate);
+            assert!((output.len() as f64 - expected).abs() <= 1.0, "{} Hz: {} samples, expected {}", rate, output.len(), expected);
+        }
+    }
+
+    #[test]
+    fn passband_amplitude_is_kept() {
+        let sine_rms = SWEEP_AMPLITUDE / 2f32.sqrt();
+        for rate in [44100, 48000, 96000] {
+            let rms = inner_rms(&resample(&sweep(rate, 100.0, 6000.0), rate));
+            assert!((rms / sine_rms - 1.0).abs() < 0.02, "{} Hz: rms {} instead of {}", rate, rms, sine_rms);
+        }
+    }
+
+    /// Largest value below full scale for a signed format with `bits` bits.
+    fn max_below_one(bits: i32) -> f32 {
+        1.0 - 2f32.powi(1 - bits)
+    }
+
+    #[test]
+    fn converts_signed_integer_formats() {
+        assert_eq!(sample_to_f32(i8::MIN), -1.0);
+        assert_eq!(sample_to_f32(0i8), 0.0);
+        assert_eq!(sample_to_f32(i8::MAX), max_below_one(8));
+
+        assert_eq!(sample_to_f32(i16::MIN), -1.0);
+        assert_eq!(sample_to_f32(0i16), 0.0);
+        assert_eq!(sample_to_f32(i16::MAX), max_below_one(16));
+
+        assert_eq!(sample_to_f32(i32::MIN), -1.0);
+        assert_eq!(sample_to_f32(0i32), 0.0);
+        assert!((sample_to_f32(i32::MAX) - 1.0).abs() < 1e-6);
+
+        assert_eq!(sample_to_f32(i64::MIN), -1.0);
+        assert_eq!(sample_to_f32(0i64), 0.0);
+        assert!((sample_to_f32(i64::MAX) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn converts_i24_with_sign_extension() {
+        assert_eq!(sample_to_f32(cpal::I24::new(-(1 << 23)).unwrap()), -1.0);
+        assert_eq!(sample_to_f32(cpal::I24::new(0).unwrap()), 0.0);
+        assert_eq!(sample_to_f32(cpal::I24::new((1 << 23) - 1).unwrap()), max_below_one(24));
+        // All 24 bits set is -1, not 2^24 - 1 read as a large positive value
+        let minus_one = sample_to_f32(cpal::I24::new(-1).unwrap());
+        assert!(minus_one < 0.0 && minus_one > -1e-6);
+    }
+
+    #[test]
+    fn converts_unsigned_formats_around_their_midpoint() {
+        assert_eq!(sample_to_f32(0u8), -1.0);
+        assert_eq!(sample_to_f32(128u8), 0.0);
+        assert_eq!(sample_to_f32(u8::MAX), max_below_one(8));
+
+        assert_eq!(sample_to_f32(0u16), -1.0);
+        assert_eq!(sample_to_f32(32768u16), 0.0);
+        assert_eq!(sample_to_f32(u16::MAX), max_below_one(16));
+
+        assert_eq!(sample_to_f32(0u32), -1.0);
+        assert_eq!(sample_to_f32(1u32 << 31), 0.0);
+        assert!((sample_to_f32(u32::MAX) - 1.0).abs() < 1e-6);
+
+        assert_eq!(sample_to_f32(0u64), -1.0);
+        assert_eq!(sample_to_f32(1u64 << 63), 0.0);
+        assert!((sample_to_f32(u64::MAX) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn converts_u24_with_offset() {
+        assert_eq!(sample_to_f32(cpal::U24::new(0).unwrap()), -1.0);
+        assert_eq!(sample_to_f32(cpal::U24::new(1 << 23).unwrap()), 0.0);
+        assert_eq!(sample_to_f32(cpal::U24::new((1 << 24) - 1).unwrap()), max_below_one(24));
+        // Just below the midpoint is just below zero
+        let below = sample_to_f32(cpal::U24::new((1 << 23) - 1).unwrap());
+        assert!(below < 0.0 && below > -1e-6);
+    }
+
+    #[test]
+    fn converts_float_formats() {
+        assert_eq!(sample_to_f32(-1.0f32), -1.0);
+        assert_eq!(sample_to_f32(0.0f32), 0.0);
+        assert_eq!(sample_to_f32(1.0f32), 1.0);
+
+        assert_eq!(sample_to_f32(-1.0f64), -1.0);
+        assert_eq!(sample_to_f32(0.0f64), 0.0);
+        assert_eq!(sample_to_f32(1.0f64), 1.0);
+        assert_eq!(sample_to_f32(0.25f64), 0.25);
+    }
+
+    #[test]
+    fn content_above_output_nyquist_is_rejected() {
+        let sine_rms = SWEEP_AMPLITUDE / 2f32.sqrt();
+        for rate in [44100, 48000, 96000] {
+            let top = rate as f64 / 2.0 * 0.95;
+            let rms = inner_rms(&resample(&sweep(rate, 9000.0, top), rate));
+            // At least 40 dB down instead of folding back into the speech band
+            assert!(rms < sine_rms * 0.01, "{} Hz: rms {} leaked through", rate, rms);
+        }
+    }
+
+    /// Records everything `make` delivers, as `stop_recording` would.
+    fn record(make: impl Fn() -> Box<dyn AudioSource> + Send + 'static) -> CapturedAudio {
+        let mut recorder = AudioRecorder::new();
+        recorder.set_source(Some(Box::new(move || Ok(make()))));
+        recorder.start(None).unwrap();
+        recorder.stop().unwrap()
+    }
+
+    fn assert_asr_format(wav: &WavPayload) {
+        let spec = wav.reader().unwrap().spec();
+        assert_eq!((spec.sample_rate, spec.channels, spec.bits_per_sample), (TARGET_SAMPLE_RATE, 1, 16));
+    }
+
+    /// Stands in for a transcription service: notes the format of every upload
+    /// and holds the first chunk longest, so chunks finish out of order.
+    struct FakeBackend {
+        max_upload: usize,
+        delay: Duration,
+        received: Mutex<Vec<(usize, WavSpec)>>,
+    }
+
+    impl TranscriptionBackend for FakeBackend {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn max_upload_bytes(&self) -> usize {
+            self.max_upload
         }
 
-        // Clear buffer
-        let len = buffer_guard.len();
-        drop(buffer_guard);
-        self.buffer.lock().unwrap().clear();
+        fn max_concurrency(&self) -> usize {
+            3
+        }
+
+        fn transcribe(&self, wav_data: Vec<u8>, chunk_idx: usize, _total: usize) -> BackendFuture<'_> {
+            Box::pin(async move {
+                let spec = WavPayload::Memory(wav_data).reader().map_err(AppError::Io)?.spec();
+                self.received.lock().unwrap().push((chunk_idx, spec));
+                tokio::time::sleep(if chunk_idx == 1 { self.delay * 4 } else { self.delay }).await;
+                Ok(FullResponse { text: format!("part{}", chunk_idx), messages: Vec::new(), language: None, words: None })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn synthetic_recording_is_trimmed_and_transcribed_in_chunk_order() {
+        let spec = "noise:0.001:1000,tone:220:0.3:3000,noise:0.001:1000";
+        let captured = record(move || Box::new(SyntheticSource::parse(spec).unwrap()));
+        assert!((captured.duration - 5.0).abs() < 0.01, "{}s captured", captured.duration);
+        assert_eq!(captured.quality.diagnosis, CaptureDiagnosis::Ok);
+        assert_asr_format(&captured.wav);
+
+        // The same steps `stop_recording` takes: trim, then upload in chunks
+        let vad_config = VadConfig::default();
+        let trimmed = vad::trim_silence(&captured.wav, &vad_config).unwrap().expect("speech detected");
+        let pad = vad_config.padding_ms as f32 / 1000.0;
+        assert!((trimmed.trimmed_sec - (3.0 + 2.0 * pad)).abs() < 0.05, "{}s after VAD", trimmed.trimmed_sec);
+
+        let backend = FakeBackend { max_upload: 32 * 1024, delay: Duration::from_millis(50), received: Mutex::new(Vec::new()) };
+        let mut finished = Vec::new();
+        let res = transcribe::upload(&backend, &trimmed.wav, |_| {}, |p| finished.push(p.chunk)).await.unwrap();
+
+        let received = backend.received.into_inner().unwrap();
+        let total = received.len();
+        assert!(total > 1, "not split");
+        assert_eq!(received.iter().map(|r| r.0).collect::<Vec<_>>(), (1..=total).collect::<Vec<_>>());
+        for (_, spec) in &received {
+            assert_eq!((spec.sample_rate, spec.channels, spec.bits_per_sample), (TARGET_SAMPLE_RATE, 1, 16));
+        }
+        assert_ne!(finished[0], 1, "first chunk should finish late: {:?}", finished);
+        assert_eq!(finished.len(), total);
+        let expected: Vec<String> = (1..=total).map(|i| format!("part{}", i)).collect();
+        assert_eq!(res.text, expected.join(" "));
+    }
+
+    #[test]
+    fn silent_synthetic_recording_is_diagnosed() {
+        let captured = record(|| Box::new(SyntheticSource::new(44100, 1).silence(1500)));
+        assert_eq!(captured.quality.diagnosis, CaptureDiagnosis::Silent);
+        assert!((captured.duration - 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn replays_a_wav_file_as_16k_mono() {
+        let file = tempfile::Builder::new().suffix(".wav").tempfile().unwrap();
+        let spec = WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(file.path(), spec).unwrap();
+        for s in sweep(44100, 200.0, 4000.0) {
+            let s = (s * i16::MAX as f32) as i16;
+            writer.write_sample(s).unwrap();
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
 
-        let duration_sec = len as f32 / self.spec.channels as f32 / self.spec.sample_rate as f32;
-        Ok((wav_data, duration_sec))
+        let path = file.path().to_path_buf();
+        let captured = record(move || Box::new(crate::source::FileSource::open(&path, false).unwrap()));
+        assert!((captured.duration as f64 - SWEEP_SECS).abs() < 0.01, "{}s captured", captured.duration);
+        assert_asr_format(&captured.wav);
+        let samples = captured.wav.reader().unwrap().len() as f64;
+        assert!((samples - SWEEP_SECS * TARGET_SAMPLE_RATE as f64).abs() <= 1.0);
     }
 }
@@ -2,6 +2,7 @@
 use sha2::{Sha256, Digest};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, State};
+use crate::error::{AppError, Server};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ClientStatus {
@@ -32,72 +33,49 @@ pub fn get_hw_id() -> String {
     format!("{:x}", hasher.finalize())
 }
 
-pub async fn check_status() -> Result<ClientStatus, String> {
+pub async fn check_status() -> Result<ClientStatus, AppError> {
     let hw_id = get_hw_id();
-    use std::time::Duration;
-    
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .unwrap_or_default();
-    
-    let api_base = if cfg!(debug_assertions) {
-        "http://127.0.0.1:3000"
-    } else {
-        "https://voice2text.runitfast.xyz"
-    };
-    
-    let api_url = format!("{}/api/client/status", api_base);
-    println!("INFO: Connecting to Auth API: {}", api_url);
 
-    let res = client.post(&api_url)
+    println!("INFO: Connecting to Auth API: {}", crate::api::url("/api/client/status"));
+
+    let res = crate::api::post("/api/client/status", crate::api::STATUS_TIMEOUT)
         .json(&serde_json::json!({ "hwId": hw_id }))
         .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     if !res.status().is_success() {
         let status_code = res.status();
         let err_text = res.text().await.unwrap_or_default();
-        return Err(format!("Server error ({}): {}", status_code, err_text));
+        return Err(AppError::from_status(Server::Voice2Text, status_code, &err_text));
     }
 
-    let status: ClientStatus = res.json().await.map_err(|e| e.to_string())?;
+    let status: ClientStatus = res.json().await?;
     Ok(status)
 
 }
 
 #[tauri::command]
-pub async fn fetch_campaigns(_app: AppHandle, state: State<'_, crate::AppState>) -> Result<serde_json::Value, String> {
+pub async fn fetch_campaigns(_app: AppHandle, state: State<'_, crate::AppState>) -> Result<serde_json::Value, AppError> {
     let token = {
         let status_guard = state.client_status.lock().unwrap();
         if let Some(ref s) = *status_guard {
             s.token.clone()
         } else {
-            return Err("Client not registered".to_string());
+            return Err(AppError::NotRegistered("Client not registered".to_string()));
         }
     };
 
-    let client = reqwest::Client::new();
-    let api_base = if cfg!(debug_assertions) {
-        "http://localhost:3000"
-    } else {
-        "https://voice2text.runitfast.xyz"
-    };
-    
-    let api_url = format!("{}/api/campaigns/fetch", api_base);
-    println!("INFO: Fetching Campaigns from: {}", api_url);
+    println!("INFO: Fetching Campaigns from: {}", crate::api::url("/api/campaigns/fetch"));
 
-    let res = client.post(&api_url)
+    let res = crate::api::post("/api/campaigns/fetch", crate::api::CAMPAIGNS_TIMEOUT)
         .header("Authorization", format!("Bearer {}", token))
         .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
     if !res.status().is_success() {
-        return Err(format!("API Error: {}", res.status()));
+        return Err(AppError::from_status(Server::Voice2Text, res.status(), ""));
     }
 
-    let data: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+    let data: serde_json::Value = res.json().await?;
     Ok(data)
 }
@@ -0,0 +1,301 @@
+// Splitting long recordings into uploads that fit a backend's size limit.
+// Cuts land in the quietest stretch before the limit so words aren't split,
+// each chunk after the first repeats a moment of audio from the previous one,
+// and words transcribed twice in that overlap are merged when the texts are joined.
+
+use std::io::{Cursor, Read};
+use crate::spool::WavPayload;
+
+// Analysis frame for choosing cut points
+const FRAME_MS: usize = 20;
+// Audio repeated at the start of each following chunk
+const OVERLAP_MS: usize = 1000;
+// How far before the size limit a quieter cut point may be chosen
+const SEARCH_MS: usize = 10_000;
+// Longest word run compared at a seam; a second of overlap rarely holds more
+const MAX_SEAM_WORDS: usize = 8;
+// Leading words of a chunk that may be a fragment cut by the start of the overlap
+const MAX_SEAM_SKIP: usize = 2;
+
+/// Sample ranges `[start, end)` for each chunk. No chunk is longer than `max_len`;
+/// each cut is placed in the quietest frame of the `window` samples before the limit,
+/// and every chunk after the first starts `overlap` samples before the previous cut.
+/// `levels` holds one energy value per `frame_len` samples of a `total` sample recording.
+pub fn plan_chunks(levels: &[f32], frame_len: usize, total: usize, max_len: usize, overlap: usize, window: usize) -> Vec<(usize, usize)> {
+    let frame_len = frame_len.max(1);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while total - start > max_len {
+        let limit = start + max_len;
+        // Never cut so early that the next chunk would not move past this one
+        let earliest = limit.saturating_sub(window).max(start + overlap + frame_len).min(limit);
+        let mut cut = limit;
+        let mut quietest = f32::INFINITY;
+        for frame in earliest.div_ceil(frame_len)..limit / frame_len {
+            let level = levels.get(frame).copied().unwrap_or(f32::INFINITY);
+            // Ties go to the later frame to keep chunks large
+            if level <= quietest {
+                quietest = level;
+                cut = frame * frame_len + frame_len / 2;
+            }
+        }
+        ranges.push((start, cut));
+        start = cut.saturating_sub(overlap).max(start + 1);
+    }
+    ranges.push((start, total));
+    ranges
+}
+
+/// Splits a 16-bit mono WAV into chunks of at most `max_bytes` each.
+/// Reads the payload twice (levels, then copy) so only one chunk is held in memory.
+pub struct ChunkSplitter<'a> {
+    samples: hound::WavIntoSamples<Box<dyn Read + Send + 'a>, i16>,
+    spec: hound::WavSpec,
+    ranges: Vec<(usize, usize)>,
+    next: usize,
+    pos: usize,
+    // Samples before `pos` that the next chunk starts with
+    carry: Vec<i16>,
+}
+
+impl<'a> ChunkSplitter<'a> {
+    pub fn new(wav: &'a WavPayload, max_bytes: usize) -> Result<Self, String> {
+        let mut reader = wav.reader()?;
+        let spec = reader.spec();
+        let rate = spec.sample_rate as usize;
+        let frame_len = (rate * FRAME_MS / 1000).max(1);
+
+        let mut levels = Vec::new();
+        let mut sum_sq = 0.0f64;
+        let mut in_frame = 0usize;
+        let mut total = 0usize;
+        for sample in reader.samples::<i16>() {
+            let v = sample.map_err(|e| e.to_string())? as f64 / 32768.0;
+            sum_sq += v * v;
+            in_frame += 1;
+            total += 1;
+            if in_frame == frame_len {
+                levels.push((sum_sq / in_frame as f64) as f32);
+                sum_sq = 0.0;
+                in_frame = 0;
+            }
+        }
+        if in_frame > 0 {
+            levels.push((sum_sq / in_frame as f64) as f32);
+        }
+
+        let bytes_per_sample = (spec.bits_per_sample / 8).max(1) as usize;
+        // Leave room for the WAV header
+        let max_len = (max_bytes.saturating_sub(44) / bytes_per_sample).max(1);
+        let ranges = plan_chunks(&levels, frame_len, total, max_len, rate * OVERLAP_MS / 1000, rate * SEARCH_MS / 1000);
+
+        Ok(Self {
+            samples: wav.reader()?.into_samples::<i16>(),
+            spec,
+            ranges,
+            next: 0,
+            pos: 0,
+            carry: Vec::new(),
+        })
+    }
+
+    /// Number of chunks the recording is split into.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Start and end of each chunk in seconds, overlap included.
+    pub fn spans(&self) -> Vec<(f32, f32)> {
+        let rate = self.spec.sample_rate.max(1) as f32;
+        self.ranges.iter().map(|&(start, end)| (start as f32 / rate, end as f32 / rate)).collect()
+    }
+
+    /// Encodes the next chunk as a WAV; `None` after the last one.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let Some(&(_, end)) = self.ranges.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        let next_start = self.ranges.get(self.next).map(|r| r.0).unwrap_or(end);
+
+        let mut chunk_wav = Vec::new();
+        {
+            let mut cursor = Cursor::new(&mut chunk_wav);
+            let mut writer = hound::WavWriter::new(&mut cursor, self.spec).map_err(|e| e.to_string())?;
+            let carry_start = self.pos - self.carry.len();
+            let mut next_carry = Vec::new();
+            for (i, &s) in self.carry.iter().enumerate() {
+                writer.write_sample(s).map_err(|e| e.to_string())?;
+                if carry_start + i >= next_start {
+                    next_carry.push(s);
+                }
+            }
+            while self.pos < end {
+                let Some(sample) = self.samples.next() else { break };
+                let s = sample.map_err(|e| e.to_string())?;
+                writer.write_sample(s).map_err(|e| e.to_string())?;
+                if self.pos >= next_start {
+                    next_carry.push(s);
+                }
+                self.pos += 1;
+            }
+            writer.finalize().map_err(|e| e.to_string())?;
+            self.carry = next_carry;
+        }
+        Ok(Some(chunk_wav))
+    }
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Appends the transcript of the next chunk, dropping the words at its start
+/// that repeat the end of `transcript` because both chunks heard the overlap.
+pub fn append_merged(transcript: &mut String, next: &str) {
+    let words: Vec<&str> = next.split_whitespace().collect();
+    if words.is_empty() {
+        return;
+    }
+
+    let mut tail: Vec<String> = transcript.split_whitespace().rev().take(MAX_SEAM_WORDS).map(normalize_word).collect();
+    tail.reverse();
+    let head: Vec<String> = words.iter().take(MAX_SEAM_WORDS + MAX_SEAM_SKIP).map(|w| normalize_word(w)).collect();
+
+    // Longest run wins; the first words of `next` may be a fragment of one the cut clipped
+    let mut duplicated = 0;
+    'search: for len in (1..=tail.len()).rev() {
+        for skip in 0..=MAX_SEAM_SKIP {
+            // A single matching word after a skip is too likely to be chance
+            if len == 1 && skip > 0 {
+                break;
+            }
+            if skip + len > head.len() {
+                break;
+            }
+            let seam = &tail[tail.len() - len..];
+            if seam == &head[skip..skip + len] && seam.iter().all(|w| !w.is_empty()) {
+                duplicated = skip + len;
+                break 'search;
+            }
+        }
+    }
+    for word in &words[duplicated..] {
+        if !transcript.is_empty() {
+            transcript.push(' ');
+        }
+        transcript.push_str(word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_covers(ranges: &[(usize, usize)], total: usize, max_len: usize) {
+        assert_eq!(ranges[0].0, 0);
+        assert_eq!(ranges[ranges.len() - 1].1, total);
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            assert!(end - start <= max_len, "chunk {} is {} long", i, end - start);
+            if i > 0 {
+                let (prev_start, prev_end) = ranges[i - 1];
+                assert!(start > prev_start && start <= prev_end, "chunk {} starts at {} after {:?}", i, start, ranges[i - 1]);
+            }
+        }
+    }
+
+    fn wav(samples: &[i16]) -> WavPayload {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut bytes = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut bytes), spec).unwrap();
+        for &s in samples {
+            writer.write_sample(s).unwrap();
+        }
+        writer.finalize().unwrap();
+        WavPayload::Memory(bytes)
+    }
+
+    fn samples_of(chunk: Vec<u8>) -> Vec<i16> {
+        hound::WavReader::new(Cursor::new(chunk)).unwrap().into_samples::<i16>().map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn cuts_in_the_quietest_frame_of_the_window() {
+        let mut levels = vec![1.0; 100];
+        // Quieter still, but further back than the window reaches
+        levels[10] = 0.0;
+        levels[25] = 0.1;
+        levels[32] = 0.2;
+        let ranges = plan_chunks(&levels, 10, 1000, 400, 50, 200);
+        assert_eq!(ranges[0], (0, 255));
+        assert_eq!(ranges[1].0, 205);
+        assert_covers(&ranges, 1000, 400);
+    }
+
+    #[test]
+    fn keeps_moving_when_the_overlap_nearly_fills_a_chunk() {
+        let levels = vec![0.5; 100];
+        for overlap in [95, 100, 250] {
+            let ranges = plan_chunks(&levels, 10, 1000, 100, overlap, 100);
+            assert_covers(&ranges, 1000, 100);
+            assert!(ranges.len() < 1000, "{} chunks with overlap {}", ranges.len(), overlap);
+        }
+    }
+
+    #[test]
+    fn recording_of_exactly_max_len_is_one_chunk() {
+        assert_eq!(plan_chunks(&[0.5; 40], 10, 400, 400, 50, 200), vec![(0, 400)]);
+        assert_eq!(plan_chunks(&[0.5; 41], 10, 401, 400, 50, 200).len(), 2);
+
+        let samples: Vec<i16> = (0..16000).map(|i| (i % 200) as i16).collect();
+        let payload = wav(&samples);
+        let exact = 44 + 2 * samples.len();
+        let mut splitter = ChunkSplitter::new(&payload, exact).unwrap();
+        assert_eq!(splitter.len(), 1);
+        assert_eq!(samples_of(splitter.next_chunk().unwrap().unwrap()), samples);
+        assert!(splitter.next_chunk().unwrap().is_none());
+        assert_eq!(ChunkSplitter::new(&payload, exact - 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn chunks_hold_their_planned_samples() {
+        let samples: Vec<i16> = (0..80000).map(|i| ((i * 7) % 3000) as i16).collect();
+        let payload = wav(&samples);
+        let mut splitter = ChunkSplitter::new(&payload, 44 + 2 * 32000).unwrap();
+        let spans = splitter.spans();
+        assert!(spans.len() > 2);
+        for (start, end) in spans {
+            let (start, end) = ((start * 16000.0).round() as usize, (end * 16000.0).round() as usize);
+            assert_eq!(samples_of(splitter.next_chunk().unwrap().unwrap()), &samples[start..end]);
+        }
+        assert!(splitter.next_chunk().unwrap().is_none());
+    }
+
+    fn merged(first: &str, next: &str) -> String {
+        let mut transcript = first.to_string();
+        append_merged(&mut transcript, next);
+        transcript
+    }
+
+    #[test]
+    fn drops_words_repeated_across_the_seam() {
+        assert_eq!(merged("see you at home", "home tonight"), "see you at home tonight");
+        assert_eq!(merged("the quick brown fox", "quick brown fox jumps over"), "the quick brown fox jumps over");
+        assert_eq!(merged("we meet at the station", "e the station tomorrow"), "we meet at the station tomorrow");
+    }
+
+    #[test]
+    fn seam_ignores_case_and_punctuation() {
+        assert_eq!(merged("Tell me, Bob.", "bob, where are you"), "Tell me, Bob. where are you");
+        assert_eq!(merged("It's late now", "its LATE now! Really"), "It's late now Really");
+    }
+
+    #[test]
+    fn keeps_everything_without_an_overlap() {
+        assert_eq!(merged("one two three", "four five"), "one two three four five");
+        assert_eq!(merged("", "first words"), "first words");
+        assert_eq!(merged("nothing follows", "  "), "nothing follows");
+        // A lone word a few positions in could be anything
+        assert_eq!(merged("open the door", "and door"), "open the door and door");
+    }
+}
@@ -0,0 +1,39 @@
+// Secrets kept in the OS credential store (Windows Credential Manager)
+// rather than in settings.json, which the webview and other programs can read.
+
+use crate::error::AppError;
+
+const SERVICE: &str = "Voice2Text";
+
+/// Key for the OpenAI-compatible transcription server.
+pub const OPENAI_API_KEY: &str = "openai-api-key";
+
+fn entry(name: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(SERVICE, name).map_err(|e| AppError::Other(format!("Credential store: {}", e)))
+}
+
+/// The stored secret, or `None` if there is none or the store can't be read.
+pub fn load(name: &str) -> Option<String> {
+    match entry(name).ok()?.get_password() {
+        Ok(secret) => Some(secret),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            println!("WARN: Could not read {} from the credential store: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Stores `secret` under `name`; an empty secret removes it.
+pub fn store(name: &str, secret: &str) -> Result<(), AppError> {
+    let entry = entry(name)?;
+    let result = if secret.is_empty() {
+        match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        }
+    } else {
+        entry.set_password(secret)
+    };
+    result.map_err(|e| AppError::Other(format!("Credential store: {}", e)))
+}
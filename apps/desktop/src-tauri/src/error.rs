@@ -0,0 +1,142 @@
+// Errors reported to the UI by commands and the `transcription-error` event.
+// Serialised as `{"kind":"Network","message":"..."}`: the UI words each kind
+// itself and keeps the message as technical detail.
+// Helpers that return `Result<_, String>` are mapped to a kind where they are called.
+
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "kind", content = "message")]
+pub enum AppError {
+    /// The selected input device (or any input device) is missing.
+    DeviceNotFound(String),
+    /// The input device could not be opened or stopped delivering audio.
+    Device(String),
+    /// The recording holds no usable signal, e.g. a muted microphone.
+    NoSignal(String),
+    /// The service could not be reached or the connection broke off.
+    Network(String),
+    /// The service rejected the token.
+    Unauthorized(String),
+    /// Free usage or the rate limit is used up.
+    QuotaExceeded(String),
+    /// The device is banned from the service.
+    Banned(String),
+    /// The service failed or sent an unusable answer.
+    ServerError(String),
+    /// This device has no token yet.
+    NotRegistered(String),
+    /// Reading or writing a local file failed.
+    Io(String),
+    /// The user cancelled the dictation.
+    Cancelled(String),
+    Other(String),
+}
+
+/// Who answered an HTTP request; only our own service issues tokens and bans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Server {
+    Voice2Text,
+    /// An OpenAI-compatible server the user configured.
+    ThirdParty,
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::DeviceNotFound(m)
+            | Self::Device(m)
+            | Self::NoSignal(m)
+            | Self::Network(m)
+            | Self::Unauthorized(m)
+            | Self::QuotaExceeded(m)
+            | Self::Banned(m)
+            | Self::ServerError(m)
+            | Self::NotRegistered(m)
+            | Self::Io(m)
+            | Self::Cancelled(m)
+            | Self::Other(m) => m,
+        }
+    }
+
+    /// Same kind, reworded message, e.g. to add which chunk failed.
+    pub fn map_message(self, f: impl FnOnce(String) -> String) -> Self {
+        match self {
+            Self::DeviceNotFound(m) => Self::DeviceNotFound(f(m)),
+            Self::Device(m) => Self::Device(f(m)),
+            Self::NoSignal(m) => Self::NoSignal(f(m)),
+            Self::Network(m) => Self::Network(f(m)),
+            Self::Unauthorized(m) => Self::Unauthorized(f(m)),
+            Self::QuotaExceeded(m) => Self::QuotaExceeded(f(m)),
+            Self::Banned(m) => Self::Banned(f(m)),
+            Self::ServerError(m) => Self::ServerError(f(m)),
+            Self::NotRegistered(m) => Self::NotRegistered(f(m)),
+            Self::Io(m) => Self::Io(f(m)),
+            Self::Cancelled(m) => Self::Cancelled(f(m)),
+            Self::Other(m) => Self::Other(f(m)),
+        }
+    }
+
+    /// Classifies an unsuccessful HTTP answer of `server`.
+    pub fn from_status(server: Server, status: reqwest::StatusCode, body: &str) -> Self {
+        let message = format!("API error ({}): {}", status, body);
+        match (server, status.as_u16()) {
+            (Server::Voice2Text, 401) => Self::Unauthorized(message),
+            (Server::Voice2Text, 403) => Self::Banned(message),
+            // A rejected key or region; the server's own message says which
+            (Server::ThirdParty, 401 | 403) => Self::Other(message),
+            (_, 402 | 429) => Self::QuotaExceeded(message),
+            _ => Self::ServerError(message),
+        }
+    }
+
+    pub fn not_registered() -> Self {
+        Self::NotRegistered("Registering... please wait.".to_string())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<AppError> for String {
+    fn from(err: AppError) -> Self {
+        err.to_string()
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(err: reqwest::Error) -> Self {
+        // An answer arrived but was an error or could not be read
+        if err.status().is_some() || err.is_decode() {
+            Self::ServerError(err.to_string())
+        } else {
+            Self::Network(err.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn only_our_service_bans() {
+        assert!(matches!(AppError::from_status(Server::Voice2Text, StatusCode::FORBIDDEN, ""), AppError::Banned(_)));
+        assert!(matches!(AppError::from_status(Server::ThirdParty, StatusCode::FORBIDDEN, ""), AppError::Other(_)));
+        assert!(matches!(AppError::from_status(Server::ThirdParty, StatusCode::UNAUTHORIZED, ""), AppError::Other(_)));
+        assert!(matches!(AppError::from_status(Server::ThirdParty, StatusCode::TOO_MANY_REQUESTS, ""), AppError::QuotaExceeded(_)));
+    }
+}
@@ -4,6 +4,8 @@ use std::io::{Write, BufReader, BufRead};
 use std::path::PathBuf;
 use chrono::Local;
 use uuid::Uuid;
+use crate::error::AppError;
+use crate::transcribe::Word;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct HistoryEntry {
@@ -11,6 +13,15 @@ pub struct HistoryEntry {
     pub timestamp: String,
     pub text: String,
     pub duration: f32,
+    /// Transcription backend that produced the text; missing on older entries.
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Language the text was recognised as (ISO 639-1), when known.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Word timing and confidence, when the backend reported them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub words: Option<Vec<Word>>,
 }
 
 pub fn get_history_path() -> PathBuf {
@@ -20,28 +31,31 @@ pub fn get_history_path() -> PathBuf {
     dir.join("history.jsonl")
 }
 
-pub fn append_to_history(text: &str, duration: f32) -> Result<(), String> {
+pub fn append_to_history(text: &str, duration: f32, backend: &str, language: Option<&str>, words: Option<&[Word]>) -> Result<(), AppError> {
     let path = get_history_path();
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open(path)
-        .map_err(|e| e.to_string())?;
+        .open(path)?;
 
     let entry = HistoryEntry {
         id: Uuid::new_v4().to_string(),
         timestamp: Local::now().to_rfc3339(),
         text: text.to_string(),
         duration,
+        backend: Some(backend.to_string()),
+        language: language.map(str::to_string),
+        words: words.map(<[Word]>::to_vec),
     };
 
-    let json = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
-    writeln!(file, "{}", json).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&entry).map_err(|e| AppError::Io(e.to_string()))?;
+    writeln!(file, "{}", json)?;
     Ok(())
 }
 
+/// All entries, oldest first.
 #[allow(clippy::lines_filter_map_ok)]
-pub fn read_history(limit: usize, offset: usize, search: Option<String>) -> Vec<HistoryEntry> {
+fn read_all() -> Vec<HistoryEntry> {
     let path = get_history_path();
     let file = match File::open(path) {
         Ok(f) => f,
@@ -49,10 +63,14 @@ pub fn read_history(limit: usize, offset: usize, search: Option<String>) -> Vec<
     };
 
     let reader = BufReader::new(file);
-    let mut entries: Vec<HistoryEntry> = reader.lines()
+    reader.lines()
         .filter_map(|line| line.ok())
         .filter_map(|line| serde_json::from_str::<HistoryEntry>(&line).ok())
-        .collect();
+        .collect()
+}
+
+pub fn read_history(limit: usize, offset: usize, search: Option<String>) -> Vec<HistoryEntry> {
+    let mut entries = read_all();
 
     // Search filter
     if let Some(query) = search {
@@ -64,7 +82,11 @@ pub fn read_history(limit: usize, offset: usize, search: Option<String>) -> Vec<
     entries.into_iter().skip(offset).take(limit).collect()
 }
 
-pub fn clear_history() -> Result<(), String> {
+pub fn get_entry(id: &str) -> Result<HistoryEntry, AppError> {
+    read_all().into_iter().find(|e| e.id == id).ok_or_else(|| AppError::Other(format!("No history entry {}", id)))
+}
+
+pub fn clear_history() -> Result<(), AppError> {
     let path = get_history_path();
-    std::fs::remove_file(path).map_err(|e| e.to_string())
+    Ok(std::fs::remove_file(path)?)
 }
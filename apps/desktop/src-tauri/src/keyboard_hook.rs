@@ -1,7 +1,10 @@
 // Keyboard Hook for PTT
-// Only intercepts F8 and Ctrl+F12. Everything else is passed through.
+// Only intercepts F8 and Ctrl+F12, plus Escape while recording and shortly after.
+// Everything else is passed through.
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use winapi::shared::minwindef::{LPARAM, LRESULT, WPARAM};
 use winapi::shared::windef::HHOOK;
 use winapi::um::winuser::{
@@ -11,10 +14,20 @@ use winapi::um::winuser::{
 
 static mut HOOK_HANDLE: HHOOK = std::ptr::null_mut();
 static RECORDING: AtomicBool = AtomicBool::new(false);
+static CANCEL_ARMED: AtomicBool = AtomicBool::new(false);
+// End of the grace period after a recording, in ms since EPOCH; 0 when none
+static CANCEL_GRACE_END: AtomicU64 = AtomicU64::new(0);
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+// Escape still cancels this long after the recording stopped, while its
+// transcription runs; later presses belong to the focused window
+const CANCEL_GRACE: Duration = Duration::from_secs(3);
 
 // Virtual Key Codes
 const VK_F8: i32 = 0x77;
 const VK_F12: i32 = 0x7B;
+const VK_ESCAPE: i32 = 0x1B;
 const VK_LCONTROL: i32 = 0xA2;
 const VK_RCONTROL: i32 = 0xA3;
 
@@ -43,6 +56,16 @@ unsafe extern "system" fn keyboard_hook_proc(code: i32, w_param: WPARAM, l_param
             }
         }
         
+        // Cancel: Escape, only while recording or just after
+        if vk_code == VK_ESCAPE && cancel_armed() {
+            if event_type == WM_KEYDOWN || event_type == WM_SYSKEYDOWN {
+                println!("[HOOK] ESC PRESSED -> CANCEL");
+                CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+                winapi::um::utilapiset::Beep(800, 50);
+            }
+            return 1; // Block key
+        }
+
         // PTT Logic: Ctrl+F12
         if vk_code == VK_F12 {
              let ctrl = (winapi::um::winuser::GetAsyncKeyState(VK_LCONTROL) as u16 & 0x8000 != 0) || 
@@ -101,6 +124,34 @@ pub fn is_recording() -> bool {
     RECORDING.load(Ordering::SeqCst)
 }
 
+fn now_ms() -> u64 {
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+fn cancel_armed() -> bool {
+    CANCEL_ARMED.load(Ordering::SeqCst) || now_ms() < CANCEL_GRACE_END.load(Ordering::SeqCst)
+}
+
+/// Escape is taken as "cancel" while recording, and for `CANCEL_GRACE` after the
+/// recording stops if its transcription is still running. Otherwise it reaches
+/// the focused window.
+pub fn set_cancel_state(recording: bool, transcribing: bool) {
+    let was_recording = CANCEL_ARMED.swap(recording, Ordering::SeqCst);
+    if recording || !transcribing {
+        CANCEL_GRACE_END.store(0, Ordering::SeqCst);
+    } else if was_recording {
+        CANCEL_GRACE_END.store(now_ms() + CANCEL_GRACE.as_millis() as u64, Ordering::SeqCst);
+    }
+    if !recording && !transcribing {
+        CANCEL_REQUESTED.store(false, Ordering::SeqCst);
+    }
+}
+
+/// True once per Escape press since the last call.
+pub fn take_cancel_request() -> bool {
+    CANCEL_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
 #[allow(dead_code)]
 pub fn uninstall_keyboard_hook() {
     unsafe {
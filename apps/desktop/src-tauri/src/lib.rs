@@ -1,9 +1,22 @@
+mod api;
 mod audio;
+mod chunking;
+mod credentials;
+pub mod error;
 mod transcribe;
 mod text_injection;
 mod auth;
 mod keyboard_hook;
 mod history;
+mod local_whisper;
+mod vocabulary;
+mod outbox;
+mod subtitles;
+mod source;
+mod streaming;
+mod spool;
+mod settings;
+mod vad;
 
 use std::sync::Mutex;
 use tauri::{
@@ -17,6 +30,7 @@ use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut,
 use std::fs::OpenOptions;
 use std::io::Write;
 use chrono::Local;
+use crate::error::AppError;
 
 pub fn write_to_log(_app: &AppHandle, message: &str) {
     let log_msg = format!("[{}] {}\n", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
@@ -41,6 +55,17 @@ macro_rules! log_info {
 use rodio::{OutputStream, Sink, Source};
 use std::time::Duration;
 
+// How often the selected input device is looked for after it went missing
+const DEVICE_POLL: Duration = Duration::from_secs(2);
+
+// Replays this WAV/FLAC file instead of the microphone, for headless runs
+const REPLAY_FILE_ENV: &str = "VOICE2TEXT_REPLAY_FILE";
+// Plays this synthetic signal instead (see `SyntheticSource::parse`)
+const SYNTHETIC_SOURCE_ENV: &str = "VOICE2TEXT_SYNTHETIC_SOURCE";
+
+// How often the outbox is checked for recordings due another attempt
+const OUTBOX_POLL: Duration = Duration::from_secs(30);
+
 pub fn play_feedback_sound(frequency: f32, duration_ms: u64) {
     std::thread::spawn(move || {
         // Rodio 0.17: OutputStream::try_default returns (stream, handle)
@@ -62,50 +87,348 @@ struct AppState {
     version: String,
     client_status: Mutex<Option<auth::ClientStatus>>,
     selected_mic: Mutex<Option<String>>,
+    settings: Mutex<settings::AppSettings>,
+    /// Live transcription of the current recording when streaming is enabled.
+    stream_session: Mutex<Option<streaming::StreamSession>>,
+    /// One sender per dictation still being transcribed; sending cancels it,
+    /// `true` keeps its audio in the outbox.
+    pending_transcriptions: Mutex<Vec<tokio::sync::oneshot::Sender<bool>>>,
+}
+
+/// Payload of the `recording-error` event.
+#[derive(Clone, serde::Serialize)]
+struct RecordingError {
+    message: String,
+    /// The recording goes on, on the system default device.
+    recovered: bool,
 }
 
 #[tauri::command]
-fn get_input_devices() -> Vec<String> {
+fn get_input_devices() -> Vec<audio::InputDeviceInfo> {
     audio::AudioRecorder::list_devices()
 }
 
 #[tauri::command]
-fn set_input_device(name: String, state: State<'_, AppState>) {
-    let mut mic_guard = state.selected_mic.lock().unwrap();
-    if name == "Default" {
-        *mic_guard = None;
-    } else {
-        *mic_guard = Some(name);
-    }
+fn set_input_device(app: AppHandle, id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    let selected = if id == "Default" { None } else { Some(id) };
+    *state.selected_mic.lock().unwrap() = selected.clone();
+
+    let snapshot = {
+        let mut settings = state.settings.lock().unwrap();
+        settings.input_device = selected;
+        settings.clone()
+    };
+    settings::save_settings(&snapshot).map_err(AppError::Io)?;
+
+    // Re-open the warm stream on the newly selected device
+    apply_warm_mic(&app);
+    Ok(())
 }
 
 #[tauri::command]
 fn get_input_device(state: State<'_, AppState>) -> Option<String> {
-    state.selected_mic.lock().unwrap().clone()
+    let selected = state.selected_mic.lock().unwrap().clone()?;
+    let devices = audio::AudioRecorder::list_devices();
+    if devices.iter().any(|d| d.id == selected) {
+        return Some(selected);
+    }
+    // Older settings stored the device name; report the matching id instead
+    devices.into_iter().find(|d| d.name == selected).map(|d| d.id).or(Some(selected))
+}
+
+#[tauri::command]
+fn get_settings(state: State<'_, AppState>) -> settings::AppSettings {
+    state.settings.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn update_settings(app: AppHandle, mut new_settings: settings::AppSettings, state: State<'_, AppState>) -> Result<(), AppError> {
+    // The API key only changes through `set_api_key`
+    {
+        let current = state.settings.lock().unwrap();
+        new_settings.transcription.openai.api_key = current.transcription.openai.api_key.clone();
+        new_settings.transcription.openai.api_key_set = current.transcription.openai.api_key_set;
+    }
+    // Reject an unusable proxy or certificate before it is saved
+    if new_settings.api != state.settings.lock().unwrap().api {
+        api::configure(&new_settings.api).map_err(AppError::Other)?;
+        log_info!(&app, "API base: {}", api::base_url());
+    }
+    settings::save_settings(&new_settings).map_err(AppError::Io)?;
+    *state.selected_mic.lock().unwrap() = new_settings.input_device.clone();
+    *state.settings.lock().unwrap() = new_settings;
+    apply_warm_mic(&app);
+    Ok(())
+}
+
+/// Stores the key for the OpenAI-compatible server in the OS credential store;
+/// an empty key removes it. Returns whether a key is set.
+#[tauri::command]
+fn set_api_key(app: AppHandle, key: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let key = key.trim().to_string();
+    credentials::store(credentials::OPENAI_API_KEY, &key)?;
+    let is_set = !key.is_empty();
+    let mut settings = state.settings.lock().unwrap();
+    settings.transcription.openai.api_key = key;
+    settings.transcription.openai.api_key_set = is_set;
+    log_info!(&app, "API key {}", if is_set { "stored" } else { "removed" });
+    Ok(is_set)
+}
+
+/// Adds the terms from a `.csv` or plain text file to the vocabulary.
+#[tauri::command]
+fn import_vocabulary(app: AppHandle, path: String, state: State<'_, AppState>) -> Result<Vec<vocabulary::VocabularyTerm>, AppError> {
+    let terms = vocabulary::import(std::path::Path::new(&path))?;
+    let imported = terms.len();
+    let snapshot = {
+        let mut settings = state.settings.lock().unwrap();
+        vocabulary::merge(&mut settings.transcription.vocabulary, terms);
+        settings.clone()
+    };
+    settings::save_settings(&snapshot).map_err(AppError::Io)?;
+    log_info!(&app, "Vocabulary: imported {} terms from {}", imported, path);
+    Ok(snapshot.transcription.vocabulary)
+}
+
+#[tauri::command]
+fn export_vocabulary(path: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    let terms = state.settings.lock().unwrap().transcription.vocabulary.clone();
+    vocabulary::export(std::path::Path::new(&path), &terms)
+}
+
+#[tauri::command]
+fn is_mic_warm(state: State<'_, AppState>) -> bool {
+    state.recorder.lock().unwrap().is_warm()
+}
+
+/// Opens or closes the warm mic to match the settings and tells the UI
+/// (event + tray tooltip) whether the microphone is live between recordings.
+fn apply_warm_mic(app: &AppHandle) {
+    let state: State<AppState> = app.state();
+    if *state.is_recording.lock().unwrap() {
+        // Applied again once the recording stops
+        return;
+    }
+
+    let warm = state.settings.lock().unwrap().warm_mic.clone();
+    let device_name = state.selected_mic.lock().unwrap().clone();
+    let is_warm = {
+        let mut recorder = state.recorder.lock().unwrap();
+        if warm.enabled {
+            if let Err(e) = recorder.warm_up(device_name, warm.clamped_preroll_ms()) {
+                log_info!(app, "Warm mic failed: {}", e);
+                recorder.cool_down();
+            }
+        } else {
+            recorder.cool_down();
+        }
+        recorder.is_warm()
+    };
+
+    let _ = app.emit("mic-warm", is_warm);
+    if let Some(tray) = app.tray_by_id("main") {
+        let tooltip = if is_warm { "Voice2Text (warm mic: microphone is open)" } else { "Voice2Text" };
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+}
+
+fn toggle_warm_mic(app: &AppHandle) {
+    let state: State<AppState> = app.state();
+    let snapshot = {
+        let mut settings = state.settings.lock().unwrap();
+        settings.warm_mic.enabled = !settings.warm_mic.enabled;
+        settings.clone()
+    };
+    if let Err(e) = settings::save_settings(&snapshot) {
+        log_info!(app, "Failed to save settings: {}", e);
+    }
+    log_info!(app, "Warm mic {}", if snapshot.warm_mic.enabled { "enabled" } else { "disabled" });
+    apply_warm_mic(app);
+}
+
+/// Source factory that plays `path` into every recording.
+fn replay_source(path: String, realtime: bool) -> Result<audio::SourceFactory, AppError> {
+    // Fail now rather than on the next hotkey press
+    source::FileSource::open(&path, realtime).map_err(AppError::Io)?;
+    Ok(Box::new(move || {
+        let file = source::FileSource::open(&path, realtime)?;
+        Ok(Box::new(file) as Box<dyn source::AudioSource>)
+    }))
+}
+
+/// Debug mode: recordings play `path` in real time instead of capturing the
+/// microphone, as if the file were spoken while the hotkey is held.
+/// `None` switches back to the microphone.
+#[tauri::command]
+fn set_replay_file(app: AppHandle, path: Option<String>, state: State<'_, AppState>) -> Result<(), AppError> {
+    if *state.is_recording.lock().unwrap() {
+        return Err(AppError::Other("Cannot change the audio source while recording".to_string()));
+    }
+    let factory = match path {
+        Some(path) => {
+            log_info!(&app, "Replay mode: recordings read {}", path);
+            Some(replay_source(path, true)?)
+        }
+        None => {
+            log_info!(&app, "Replay mode off, using the microphone");
+            None
+        }
+    };
+    state.recorder.lock().unwrap().set_source(factory);
+    apply_warm_mic(&app);
+    Ok(())
+}
+
+/// Reacts to errors reported by the audio backend. Losing the device
+/// mid-recording either moves the recording to the default device or
+/// aborts it, so a dead stream never ends up being uploaded.
+fn handle_stream_failure(app: &AppHandle, failure: source::StreamFailure) {
+    if !failure.fatal {
+        log_info!(app, "Audio stream glitch: {}", failure.message);
+        return;
+    }
+
+    let state: State<AppState> = app.state();
+    let failover = state.settings.lock().unwrap().device_failover;
+    let mut is_recording = state.is_recording.lock().unwrap();
+    let mut recorder = state.recorder.lock().unwrap();
+    if !recorder.is_current_stream(failure.generation) {
+        // Error from a stream that has already been replaced
+        return;
+    }
+    log_info!(app, "Input stream lost: {}", failure.message);
+
+    if !*is_recording {
+        // Only the warm stream died; it is re-opened when the device returns
+        recorder.cool_down();
+        drop(recorder);
+        drop(is_recording);
+        apply_warm_mic(app);
+        return;
+    }
+
+    let recovered = failover && match recorder.fail_over(None) {
+        Ok(()) => true,
+        Err(e) => {
+            log_info!(app, "Failover to default device failed: {}", e);
+            false
+        }
+    };
+    if !recovered {
+        recorder.abort();
+        *is_recording = false;
+        if let Some(session) = state.stream_session.lock().unwrap().take() {
+            session.cancel();
+        }
+    }
+    drop(recorder);
+    drop(is_recording);
+    if !recovered {
+        update_cancel_state(app, false);
+    }
+
+    if recovered {
+        log_info!(app, "Recording continues on the default input device");
+    } else {
+        log_info!(app, "Recording status: ABORTED (input device lost)");
+        play_feedback_sound(200.0, 300);
+        let _ = app.emit("recording-state", false);
+        apply_warm_mic(app);
+    }
+    let _ = app.emit("recording-error", RecordingError { message: failure.message, recovered });
+}
+
+/// Polls for the selected input device while it is missing, and re-opens
+/// the warm mic on it (and tells the UI) once it is plugged back in.
+/// Recordings pick it up again on their own since they start on `selected_mic`.
+fn watch_selected_device(app: AppHandle) {
+    let mut watched: Option<String> = None;
+    let mut was_available = true;
+    loop {
+        std::thread::sleep(DEVICE_POLL);
+        let state: State<AppState> = app.state();
+        let Some(selected) = state.selected_mic.lock().unwrap().clone() else {
+            watched = None;
+            continue;
+        };
+        if watched.as_ref() != Some(&selected) {
+            watched = Some(selected.clone());
+            was_available = true;
+        }
+
+        let available = audio::AudioRecorder::device_available(&selected);
+        if available == was_available {
+            continue;
+        }
+        was_available = available;
+        if available {
+            log_info!(&app, "Input device is back: {}", selected);
+            let _ = app.emit("input-device-restored", selected);
+            apply_warm_mic(&app);
+        } else {
+            log_info!(&app, "Input device disappeared: {}", selected);
+        }
+    }
 }
 
 #[tauri::command]
-async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), AppError> {
     let mut is_recording_guard = state.is_recording.lock().unwrap();
     if *is_recording_guard { return Ok(()); }
 
     // Check validity
-    let _token = {
+    let token = {
         let status_guard = state.client_status.lock().unwrap();
         if let Some(ref s) = *status_guard {
-            if s.status == "banned" { return Err("Device is banned.".to_string()); }
+            if s.status == "banned" { return Err(AppError::Banned("Device is banned.".to_string())); }
             s.token.clone()
         } else {
-            return Err("Registering... please wait.".to_string());
+            return Err(AppError::not_registered());
         }
     };
 
     let device_name = state.selected_mic.lock().unwrap().clone();
     let log_dev = device_name.clone().unwrap_or("Default".to_string());
+    let (failover, streaming, language, vocabulary) = {
+        let settings = state.settings.lock().unwrap();
+        let transcription = &settings.transcription;
+        (settings.device_failover, transcription.streaming.clone(), transcription.language.clone(), transcription.vocabulary.clone())
+    };
     
     log_info!(&app, "Attempting to start recording with device: {}", log_dev);
-    state.recorder.lock().unwrap().start(device_name)?;
+    {
+        let mut recorder = state.recorder.lock().unwrap();
+        let levels = recorder.subscribe_levels();
+        let frames = streaming.enabled.then(|| recorder.subscribe_frames());
+        if let Err(e) = recorder.start(device_name.clone()) {
+            if device_name.is_none() || !failover {
+                return Err(e);
+            }
+            log_info!(&app, "Selected device unavailable ({}), recording on default device", e);
+            recorder.start(None)?;
+            let _ = app.emit("recording-error", RecordingError { message: e.to_string(), recovered: true });
+        }
+
+        // Forward meter readings until the stream is dropped in stop()
+        let level_handle = app.clone();
+        std::thread::spawn(move || {
+            for level in levels {
+                let _ = level_handle.emit("audio-level", level);
+            }
+        });
+
+        if let Some(frames) = frames {
+            let url = streaming.endpoint();
+            log_info!(&app, "Streaming transcription to {}", url);
+            let partial_handle = app.clone();
+            let session = streaming::StreamSession::start(url, token, language, vocabulary, frames, move |partial| {
+                let _ = partial_handle.emit("transcription-partial", partial);
+            });
+            *state.stream_session.lock().unwrap() = Some(session);
+        }
+    }
     *is_recording_guard = true;
+    update_cancel_state(&app, true);
     log_info!(&app, "Recording status: STARTED");
     play_feedback_sound(440.0, 150); // A4 (Start)
     let _ = app.emit("recording-state", true);
@@ -113,7 +436,7 @@ async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(
 }
 
 #[tauri::command]
-async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), AppError> {
     let mut is_recording_guard = state.is_recording.lock().unwrap();
     if !*is_recording_guard { return Ok(()); }
 
@@ -122,7 +445,7 @@ async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<()
         if let Some(ref s) = *status_guard {
             s.token.clone()
         } else {
-            return Err("Registering... please wait.".to_string());
+            return Err(AppError::not_registered());
         }
     };
 
@@ -131,13 +454,100 @@ async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<()
     play_feedback_sound(300.0, 100); // Lower tone (Stop)
     let _ = app.emit("recording-state", false);
 
-    let (wav_data, duration) = state.recorder.lock().unwrap().stop()?;
+    let session = state.stream_session.lock().unwrap().take();
+    let captured = match state.recorder.lock().unwrap().stop() {
+        Ok(captured) => captured,
+        Err(e) => {
+            if let Some(session) = session { session.cancel(); }
+            update_cancel_state(&app, false);
+            return Err(e);
+        }
+    };
+    // Registered before the recording flag is released, so Escape stays armed until the text is typed
+    let (cancel_tx, cancel_rx) = tokio::sync::oneshot::channel();
+    state.pending_transcriptions.lock().unwrap().push(cancel_tx);
+    update_cancel_state(&app, false);
+    drop(is_recording_guard);
+    // Pick up warm mic changes made while recording
+    apply_warm_mic(&app);
+
+    let duration = captured.duration;
+    let quality = captured.quality;
+    log_info!(&app, "Capture quality: {:?} (peak {:.3}, rms {:.4}, floor {:.1} dB, clipped {:.4}, speech {:.2})",
+        quality.diagnosis, quality.peak, quality.rms, quality.noise_floor_db, quality.clipped_ratio, quality.speech_ratio);
+    let _ = app.emit("recording-quality", quality.clone());
+
+    if quality.diagnosis == audio::CaptureDiagnosis::Silent {
+        log_info!(&app, "Recording is silent, skipping upload.");
+        if let Some(session) = session { session.cancel(); }
+        drop(cancel_rx);
+        update_cancel_state(&app, false);
+        let _ = app.emit("transcription-error", AppError::NoSignal("The recording is silent".to_string()));
+        return Ok(());
+    }
+    let wav = captured.wav;
+
+    let vad_config = state.settings.lock().unwrap().vad.clone();
+    let (wav, time_map) = if vad_config.enabled {
+        match vad::trim_silence(&wav, &vad_config) {
+            Ok(Some(res)) => {
+                log_info!(&app, "VAD: trimmed {:.2}s -> {:.2}s", res.original_sec, res.trimmed_sec);
+                (res.wav, res.time_map)
+            }
+            Ok(None) => {
+                log_info!(&app, "VAD: no speech regions found, sending untrimmed audio");
+                (wav, vad::TimeMap::default())
+            }
+            Err(e) => {
+                log_info!(&app, "VAD failed, sending untrimmed audio: {}", e);
+                (wav, vad::TimeMap::default())
+            }
+        }
+    } else {
+        (wav, vad::TimeMap::default())
+    };
     
+    let transcription = state.settings.lock().unwrap().transcription.clone();
+    let backend = transcribe::backend_from_config(&app, &transcription, &token);
+    log_info!(&app, "Transcription backend: {}", backend.name());
+
     let app_handle = app.clone();
     tauri::async_runtime::spawn(async move {
-        match transcribe::send_to_api(&app_handle, wav_data, &token).await {
-            Ok(data) => {
-                 log_info!(&app_handle, "Transcription success.");
+        let transcribe = async {
+            // The streamed result is usually ready already; the upload is the fallback
+            let streamed = match session {
+                Some(session) => match session.finish().await {
+                    Ok(data) => Some((data, "stream")),
+                    Err(e) => {
+                        log_info!(&app_handle, "Streaming failed, uploading the recording instead: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            match streamed {
+                Some(done) => Ok(done),
+                None => transcribe::transcribe_with_fallback(&app_handle, &transcription, backend.as_ref(), &wav).await,
+            }
+        };
+        // Cancelling drops `transcribe`, which aborts the requests still in flight
+        let (result, keep_audio) = tokio::select! {
+            biased;
+            Ok(keep_audio) = cancel_rx => (Err(AppError::Cancelled("Cancelled by the user".to_string())), keep_audio),
+            result = transcribe => (result, false),
+        };
+        let recording = *app_handle.state::<AppState>().is_recording.lock().unwrap();
+        update_cancel_state(&app_handle, recording);
+
+        match result {
+            Ok((mut data, used_backend)) => {
+                 // Uploads were timed on the trimmed audio; the stream heard it all
+                 if used_backend != "stream" {
+                     if let Some(words) = data.words.as_mut() {
+                         time_map.restore_words(words);
+                     }
+                 }
+                 log_info!(&app_handle, "Transcription success (language: {}).", data.language.as_deref().unwrap_or("unknown"));
                  let _ = app_handle.emit("transcription-result", data.text.clone());
                  crate::play_feedback_sound(880.0, 100); 
                  
@@ -147,15 +557,28 @@ async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<()
                  }
 
                  // Save to History
-                 let _ = history::append_to_history(&data.text, duration);
+                 let _ = history::append_to_history(&data.text, duration, used_backend, data.language.as_deref(), data.words.as_deref());
 
                  match text_injection::inject_text(&data.text) {
                      Ok(_) => log_info!(&app_handle, "Text Injection: SUCCESS"),
                      Err(e) => log_info!(&app_handle, "Text Injection ERROR: {}", e),
                  }
+
+                 // The service is reachable again; send what piled up while it wasn't
+                 if used_backend != "local" {
+                     flush_outbox(&app_handle).await;
+                 }
+             }
+             Err(AppError::Cancelled(message)) => {
+                 log_info!(&app_handle, "Transcription cancelled.");
+                 if keep_audio {
+                     keep_in_outbox(&app_handle, outbox::save_cancelled(&wav, &time_map, duration));
+                 }
+                 let _ = app_handle.emit("transcription-error", AppError::Cancelled(message));
              }
              Err(e) => {
                  log_info!(&app_handle, "Backend error: {}", e);
+                 keep_in_outbox(&app_handle, outbox::save(&wav, &time_map, duration, e.message()));
                  let _ = app_handle.emit("transcription-error", e);
              }
          }
@@ -164,8 +587,80 @@ async fn stop_recording(app: AppHandle, state: State<'_, AppState>) -> Result<()
      Ok(())
 }
 
+fn keep_in_outbox(app: &AppHandle, saved: Result<outbox::OutboxEntry, String>) {
+    match saved {
+        Ok(entry) => {
+            log_info!(app, "Recording kept in outbox as {}", entry.id);
+            let _ = app.emit("outbox-changed", ());
+        }
+        Err(e) => log_info!(app, "Could not save recording to outbox: {}", e),
+    }
+}
+
+/// Arms Escape in the keyboard hook while a dictation is recording or has just
+/// been handed to transcription, and tells the UI whether transcriptions are running.
+fn update_cancel_state(app: &AppHandle, recording: bool) {
+    let transcribing = {
+        let mut pending = app.state::<AppState>().pending_transcriptions.lock().unwrap();
+        // Finished transcriptions have dropped their receiver
+        pending.retain(|tx| !tx.is_closed());
+        !pending.is_empty()
+    };
+    keyboard_hook::set_cancel_state(recording, transcribing);
+    let _ = app.emit("transcribing", transcribing);
+}
+
+/// Abandons the current dictation so nothing gets typed: a running recording is
+/// stopped, and transcriptions still in progress are dropped along with their
+/// requests. With `keep_audio` (default: the `keep_cancelled` setting) the audio
+/// goes to the outbox instead of being discarded. Returns whether anything was cancelled.
 #[tauri::command]
-async fn toggle_recording(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+fn cancel_transcription(app: AppHandle, keep_audio: Option<bool>, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let keep_audio = keep_audio.unwrap_or_else(|| state.settings.lock().unwrap().keep_cancelled);
+
+    let mut is_recording = state.is_recording.lock().unwrap();
+    let was_recording = *is_recording;
+    if was_recording {
+        *is_recording = false;
+        if let Some(session) = state.stream_session.lock().unwrap().take() {
+            session.cancel();
+        }
+        let mut recorder = state.recorder.lock().unwrap();
+        if keep_audio {
+            match recorder.stop() {
+                Ok(captured) => keep_in_outbox(&app, outbox::save_cancelled(&captured.wav, &vad::TimeMap::default(), captured.duration)),
+                Err(e) => log_info!(&app, "Could not keep the cancelled recording: {}", e),
+            }
+        } else {
+            recorder.abort();
+        }
+    }
+    drop(is_recording);
+
+    // Each transcription reports its own cancellation once it has stopped
+    let mut cancelled = 0;
+    for tx in state.pending_transcriptions.lock().unwrap().drain(..) {
+        if tx.send(keep_audio).is_ok() {
+            cancelled += 1;
+        }
+    }
+    update_cancel_state(&app, false);
+
+    if was_recording {
+        log_info!(&app, "Recording status: CANCELLED");
+        play_feedback_sound(200.0, 150);
+        let _ = app.emit("recording-state", false);
+        let _ = app.emit("transcription-error", AppError::Cancelled("Recording cancelled by the user".to_string()));
+        apply_warm_mic(&app);
+    }
+    if cancelled > 0 {
+        log_info!(&app, "Cancelling {} transcription(s)", cancelled);
+    }
+    Ok(was_recording || cancelled > 0)
+}
+
+#[tauri::command]
+async fn toggle_recording(app: AppHandle, state: State<'_, AppState>) -> Result<String, AppError> {
     let is_recording = *state.is_recording.lock().unwrap();
     if !is_recording {
         start_recording(app, state).await?;
@@ -181,12 +676,18 @@ fn get_version(state: State<'_, AppState>) -> String {
     state.version.clone()
 }
 
+/// Whether this build can transcribe offline (the `local-whisper` feature).
 #[tauri::command]
-async fn open_data_folder(_app: AppHandle) -> Result<(), String> {
+fn has_local_whisper() -> bool {
+    cfg!(feature = "local-whisper")
+}
+
+#[tauri::command]
+async fn open_data_folder(_app: AppHandle) -> Result<(), AppError> {
     if let Ok(appdata) = std::env::var("LOCALAPPDATA") {
         let log_dir = std::path::Path::new(&appdata).join("Voice2Text").join("logs");
         let _ = std::fs::create_dir_all(&log_dir);
-        std::process::Command::new("explorer").arg(&log_dir).spawn().map_err(|e| e.to_string())?;
+        std::process::Command::new("explorer").arg(&log_dir).spawn()?;
     }
     Ok(())
 }
@@ -196,17 +697,18 @@ fn get_client_status(state: State<'_, AppState>) -> Option<auth::ClientStatus> {
     state.client_status.lock().unwrap().clone()
 }
 
-#[tauri::command]
-async fn refresh_client_status(app: AppHandle, state: State<'_, AppState>) -> Result<auth::ClientStatus, String> {
-    let app_handle = app.clone();
+/// Asks the service for this device's status, which also issues a fresh token,
+/// and keeps it. Runs on the UI's poll and when an upload's token was rejected.
+async fn refresh_status(app: &AppHandle) -> Result<auth::ClientStatus, AppError> {
     let res = auth::check_status().await;
     match res {
         Ok(status) => {
+             let state = app.state::<AppState>();
              let mut guard = state.client_status.lock().unwrap();
              let old_status = guard.clone().map(|s| s.status).unwrap_or_default();
              
              if old_status != status.status {
-                 log_info!(&app_handle, "Status changed: {} -> {}", old_status, status.status);
+                 log_info!(app, "Status changed: {} -> {}", old_status, status.status);
              }
              *guard = Some(status.clone());
              Ok(status)
@@ -214,12 +716,17 @@ async fn refresh_client_status(app: AppHandle, state: State<'_, AppState>) -> Re
         Err(e) => {
             // Only log errors if they are NOT network temporary issues to avoid spam?
             // For now, log errors but keep success silent.
-            log_info!(&app_handle, "Status refresh failed: {}", e);
+            log_info!(app, "Status refresh failed: {}", e);
             Err(e)
         }
     }
 }
 
+#[tauri::command]
+async fn refresh_client_status(app: AppHandle) -> Result<auth::ClientStatus, AppError> {
+    refresh_status(&app).await
+}
+
 #[tauri::command]
 fn get_hw_id() -> String {
     auth::get_hw_id()
@@ -231,10 +738,152 @@ fn get_history(limit: usize, offset: usize, search: Option<String>) -> Vec<histo
 }
 
 #[tauri::command]
-fn clear_all_history() -> Result<(), String> {
+fn clear_all_history() -> Result<(), AppError> {
     history::clear_history()
 }
 
+fn no_word_timing() -> AppError {
+    AppError::Other("The transcription service did not report word timing, so no subtitles can be made.".to_string())
+}
+
+/// Writes a history entry as subtitles, `.srt` or `.vtt` by the extension of `path`.
+#[tauri::command]
+fn export_subtitles(id: String, path: String) -> Result<usize, AppError> {
+    let words = history::get_entry(&id)?.words.ok_or_else(no_word_timing)?;
+    subtitles::write(std::path::Path::new(&path), &words)
+}
+
+/// Decodes a WAV or FLAC file through the capture pipeline, as fast as it reads,
+/// into the same 16 kHz mono WAV a recording produces.
+fn decode_audio_file(path: String) -> Result<audio::CapturedAudio, AppError> {
+    let mut recorder = audio::AudioRecorder::new();
+    recorder.set_source(Some(replay_source(path, false)?));
+    recorder.start(None)?;
+    recorder.stop()
+}
+
+/// Transcribes a WAV or FLAC file and writes subtitles for it to `output`.
+/// The transcript also lands in history, so it can be exported again later.
+#[tauri::command]
+async fn transcribe_file_subtitles(app: AppHandle, input: String, output: String) -> Result<usize, AppError> {
+    let output = std::path::PathBuf::from(output);
+    // Reject a bad output name before spending time on the transcription
+    subtitles::SubtitleFormat::from_path(&output).map_err(AppError::Other)?;
+
+    log_info!(&app, "Subtitles: transcribing {}", input);
+    let captured = tauri::async_runtime::spawn_blocking(move || decode_audio_file(input))
+        .await
+        .map_err(|e| AppError::Other(e.to_string()))??;
+    if captured.quality.diagnosis == audio::CaptureDiagnosis::Silent {
+        return Err(AppError::NoSignal("The audio file is silent".to_string()));
+    }
+
+    let (token, transcription) = {
+        let state = app.state::<AppState>();
+        let token = state.client_status.lock().unwrap().as_ref().map(|s| s.token.clone());
+        let transcription = state.settings.lock().unwrap().transcription.clone();
+        (token, transcription)
+    };
+    let token = token.ok_or_else(AppError::not_registered)?;
+    let backend = transcribe::backend_from_config(&app, &transcription, &token);
+    let (data, used_backend) = transcribe::transcribe_with_fallback(&app, &transcription, backend.as_ref(), &captured.wav).await?;
+    history::append_to_history(&data.text, captured.duration, used_backend, data.language.as_deref(), data.words.as_deref())?;
+
+    let words = data.words.ok_or_else(no_word_timing)?;
+    let cues = subtitles::write(&output, &words)?;
+    log_info!(&app, "Subtitles: wrote {} cues to {}", cues, output.display());
+    Ok(cues)
+}
+
+/// Transcribes an outbox recording again with the current settings. On success
+/// it lands in history and leaves the outbox; on failure the entry notes the error.
+async fn retry_outbox_entry(app: &AppHandle, id: &str) -> Result<String, AppError> {
+    let Some(_claim) = outbox::claim(id) else {
+        return Err(AppError::Other("This recording is already being retried.".to_string()));
+    };
+    let entry = outbox::get(id).map_err(AppError::Other)?;
+    let wav = outbox::load_wav(id).map_err(AppError::Io)?;
+
+    let (token, transcription) = {
+        let state = app.state::<AppState>();
+        let token = state.client_status.lock().unwrap().as_ref().map(|s| s.token.clone());
+        let transcription = state.settings.lock().unwrap().transcription.clone();
+        (token, transcription)
+    };
+    let token = token.ok_or_else(AppError::not_registered)?;
+    let backend = transcribe::backend_from_config(app, &transcription, &token);
+
+    let result = transcribe::transcribe_with_fallback(app, &transcription, backend.as_ref(), &wav).await;
+    let outcome = match result {
+        Ok((mut data, used_backend)) => {
+            log_info!(app, "Outbox: {} transcribed via {}", id, used_backend);
+            if let Some(words) = data.words.as_mut() {
+                entry.time_map.restore_words(words);
+            }
+            history::append_to_history(&data.text, entry.duration, used_backend, data.language.as_deref(), data.words.as_deref())?;
+            outbox::remove(id).map_err(AppError::Io)?;
+            if !data.messages.is_empty() {
+                let _ = app.emit("new-messages", data.messages);
+            }
+            Ok(data.text)
+        }
+        Err(e) => {
+            let _ = outbox::record_failure(id, e.message());
+            Err(e)
+        }
+    };
+    let _ = app.emit("outbox-changed", ());
+    outcome
+}
+
+/// Retries outbox entries oldest first, stopping at the first failure since the
+/// service is then most likely still unreachable. Entries still in their retry
+/// backoff are skipped.
+async fn flush_outbox(app: &AppHandle) {
+    for entry in outbox::list() {
+        // Cancelled dictations are never due; they wait for the user to ask
+        if !entry.is_due() {
+            continue;
+        }
+        if let Err(e) = retry_outbox_entry(app, &entry.id).await {
+            log_info!(app, "Outbox: retry of {} failed: {}", entry.id, e);
+            break;
+        }
+    }
+}
+
+async fn watch_outbox(app: AppHandle) {
+    loop {
+        tokio::time::sleep(OUTBOX_POLL).await;
+        // Leave the connection to the dictation in progress
+        if *app.state::<AppState>().is_recording.lock().unwrap() {
+            continue;
+        }
+        flush_outbox(&app).await;
+    }
+}
+
+#[tauri::command]
+fn list_outbox() -> Vec<outbox::OutboxEntry> {
+    outbox::list()
+}
+
+#[tauri::command]
+async fn retry_outbox(app: AppHandle, id: String) -> Result<String, AppError> {
+    retry_outbox_entry(&app, &id).await
+}
+
+#[tauri::command]
+fn discard_outbox(app: AppHandle, id: String) -> Result<(), AppError> {
+    let Some(_claim) = outbox::claim(&id) else {
+        return Err(AppError::Other("This recording is being retried right now.".to_string()));
+    };
+    outbox::remove(&id).map_err(AppError::Io)?;
+    log_info!(&app, "Outbox: discarded {}", id);
+    let _ = app.emit("outbox-changed", ());
+    Ok(())
+}
+
 fn is_admin() -> bool {
     #[cfg(windows)]
     {
@@ -264,15 +913,63 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(tauri_plugin_http::init())
-        .manage(AppState {
-            recorder: Mutex::new(audio::AudioRecorder::new()),
-            is_recording: Mutex::new(false),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            client_status: Mutex::new(None),
-            selected_mic: Mutex::new(None),
+        .manage({
+            let saved = settings::load_settings();
+            AppState {
+                recorder: Mutex::new(audio::AudioRecorder::new()),
+                is_recording: Mutex::new(false),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                client_status: Mutex::new(None),
+                selected_mic: Mutex::new(saved.input_device.clone()),
+                settings: Mutex::new(saved),
+                stream_session: Mutex::new(None),
+                pending_transcriptions: Mutex::new(Vec::new()),
+            }
         })
         .setup(|app| {
             let app_handle = app.handle().clone();
+
+            // Stream errors from the audio backend feed the recording state machine
+            let (failure_tx, failure_rx) = std::sync::mpsc::channel();
+            app.state::<AppState>().recorder.lock().unwrap().set_error_sender(failure_tx);
+            let failure_handle = app_handle.clone();
+            std::thread::spawn(move || {
+                for failure in failure_rx {
+                    handle_stream_failure(&failure_handle, failure);
+                }
+            });
+            let watch_handle = app_handle.clone();
+            std::thread::spawn(move || watch_selected_device(watch_handle));
+
+            let api_config = app.state::<AppState>().settings.lock().unwrap().api.clone();
+            if let Err(e) = api::configure(&api_config) {
+                log_info!(&app_handle, "Network settings ignored: {}", e);
+            }
+            log_info!(&app_handle, "API base: {}", api::base_url());
+            tauri::async_runtime::spawn(watch_outbox(app_handle.clone()));
+
+            // Headless runs: the whole file is captured on every stop, however short the recording
+            if let Ok(path) = std::env::var(REPLAY_FILE_ENV) {
+                match replay_source(path.clone(), false) {
+                    Ok(factory) => {
+                        app.state::<AppState>().recorder.lock().unwrap().set_source(Some(factory));
+                        log_info!(&app_handle, "Replaying {} instead of the microphone", path);
+                    }
+                    Err(e) => log_info!(&app_handle, "Cannot replay {}: {}", path, e),
+                }
+            } else if let Ok(spec) = std::env::var(SYNTHETIC_SOURCE_ENV) {
+                match source::SyntheticSource::parse(&spec) {
+                    Ok(_) => {
+                        let factory: audio::SourceFactory = Box::new(move || {
+                            let synthetic = source::SyntheticSource::parse(&spec)?;
+                            Ok(Box::new(synthetic) as Box<dyn source::AudioSource>)
+                        });
+                        app.state::<AppState>().recorder.lock().unwrap().set_source(Some(factory));
+                        log_info!(&app_handle, "Playing a synthetic signal instead of the microphone");
+                    }
+                    Err(e) => log_info!(&app_handle, "Cannot play synthetic signal: {}", e),
+                }
+            }
             
             // Registration
             let reg_handle = app_handle.clone();
@@ -290,6 +987,11 @@ pub fn run() {
                             let mut was_recording = false;
                             loop {
                                 std::thread::sleep(std::time::Duration::from_millis(100));
+                                if keyboard_hook::take_cancel_request() {
+                                    if let Err(e) = cancel_transcription(poll_handle.clone(), None, poll_handle.state()) {
+                                        crate::write_to_log(&poll_handle, &format!("Hook cancel failed: {}", e));
+                                    }
+                                }
                                 let is_recording = keyboard_hook::is_recording();
                                 
                                 if is_recording != was_recording {
@@ -344,13 +1046,16 @@ pub fn run() {
             // Tray
             let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let logs_i = MenuItem::with_id(app, "logs", "Open Logs", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&logs_i, &quit_i])?;
+            let warm_i = MenuItem::with_id(app, "warm_mic", "Toggle Warm Mic", true, None::<&str>)?;
+            let menu = Menu::with_items(app, &[&logs_i, &warm_i, &quit_i])?;
 
-            let _tray = TrayIconBuilder::new()
+            let _tray = TrayIconBuilder::with_id("main")
                 .icon(app.default_window_icon().unwrap().clone())
+                .tooltip("Voice2Text")
                 .menu(&menu)
                 .on_menu_event(|app, event| match event.id.as_ref() {
                     "quit" => app.exit(0),
+                    "warm_mic" => toggle_warm_mic(app),
                     "logs" => {
                         let app_handle = app.clone();
                         tauri::async_runtime::spawn(async move {
@@ -374,6 +1079,8 @@ pub fn run() {
                 })
                 .build(app)?;
 
+            apply_warm_mic(&app_handle);
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -381,6 +1088,7 @@ pub fn run() {
             stop_recording,
             toggle_recording, 
             get_version, 
+            has_local_whisper,
             open_data_folder, 
             auth::fetch_campaigns,
             get_client_status,
@@ -390,7 +1098,20 @@ pub fn run() {
             get_input_device,
             get_hw_id,
             get_history,
-            clear_all_history
+            clear_all_history,
+            get_settings,
+            update_settings,
+            set_api_key,
+            is_mic_warm,
+            set_replay_file,
+            import_vocabulary,
+            export_vocabulary,
+            export_subtitles,
+            transcribe_file_subtitles,
+            list_outbox,
+            retry_outbox,
+            discard_outbox,
+            cancel_transcription
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
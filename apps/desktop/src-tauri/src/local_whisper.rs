@@ -0,0 +1,335 @@
+// Offline transcription with whisper.cpp on the CPU.
+// Used as a backend of its own or as the fallback when the remote service fails.
+// The engine is only built with the `local-whisper` feature; the settings always are.
+
+use serde::{Serialize, Deserialize};
+use crate::error::AppError;
+use crate::transcribe::{BackendFuture, TranscriptionBackend};
+#[cfg(feature = "local-whisper")]
+use std::ffi::c_void;
+#[cfg(feature = "local-whisper")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "local-whisper")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "local-whisper")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "local-whisper")]
+use tauri::AppHandle;
+#[cfg(feature = "local-whisper")]
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState, WhisperTokenId};
+#[cfg(feature = "local-whisper")]
+use crate::audio::{Resampler, TARGET_SAMPLE_RATE};
+#[cfg(feature = "local-whisper")]
+use crate::transcribe::{FullResponse, LanguageConfig, Word};
+
+// Upper bound for automatic thread selection; more threads rarely help whisper.cpp
+#[cfg(feature = "local-whisper")]
+const MAX_AUTO_THREADS: usize = 8;
+
+/// whisper.cpp abort callback; `abort` points at the flag of one decode.
+#[cfg(feature = "local-whisper")]
+unsafe extern "C" fn abort_requested(abort: *mut c_void) -> bool {
+    (*(abort as *const AtomicBool)).load(Ordering::SeqCst)
+}
+
+/// Raises the flag of a decode once the transcription waiting for it is gone.
+/// Dropping that future does not reach the blocking thread running whisper.cpp,
+/// so cancelling one dictation stops its own decode and no other.
+#[cfg(feature = "local-whisper")]
+struct AbortOnDrop(Arc<AtomicBool>);
+
+#[cfg(feature = "local-whisper")]
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LocalWhisperConfig {
+    /// Path to a ggml/gguf Whisper model, e.g. `ggml-base.bin`. Empty disables local transcription.
+    pub model_path: String,
+    /// CPU threads used for decoding. 0 picks a count from the available cores.
+    pub threads: u32,
+    /// Transcribe locally when the selected remote service fails.
+    pub fallback: bool,
+}
+
+impl Default for LocalWhisperConfig {
+    fn default() -> Self {
+        Self { model_path: String::new(), threads: 0, fallback: true }
+    }
+}
+
+impl LocalWhisperConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.model_path.trim().is_empty()
+    }
+
+    #[cfg(feature = "local-whisper")]
+    fn thread_count(&self) -> i32 {
+        if self.threads > 0 {
+            return self.threads as i32;
+        }
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        cores.min(MAX_AUTO_THREADS) as i32
+    }
+}
+
+// The last loaded model; loading takes seconds, so it is kept between recordings
+#[cfg(feature = "local-whisper")]
+static MODEL: Mutex<Option<(PathBuf, Arc<WhisperContext>)>> = Mutex::new(None);
+
+#[cfg(feature = "local-whisper")]
+fn load_model(path: &Path) -> Result<Arc<WhisperContext>, String> {
+    let mut cached = MODEL.lock().unwrap();
+    if let Some((loaded, ctx)) = cached.as_ref() {
+        if loaded == path {
+            return Ok(ctx.clone());
+        }
+    }
+    if !path.is_file() {
+        return Err(format!("Whisper model not found: {}", path.display()));
+    }
+    let ctx = WhisperContext::new_with_params(path, WhisperContextParameters::default())
+        .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+    let ctx = Arc::new(ctx);
+    *cached = Some((path.to_path_buf(), ctx.clone()));
+    Ok(ctx)
+}
+
+/// Decodes a WAV into 16 kHz mono f32, the input format whisper.cpp expects.
+#[cfg(feature = "local-whisper")]
+fn wav_to_samples(wav_data: &[u8]) -> Result<Vec<f32>, String> {
+    let mut reader = hound::WavReader::new(std::io::Cursor::new(wav_data)).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>().map_err(|e| e.to_string())?,
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let mono: Vec<f32> = interleaved.chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+    if spec.sample_rate == TARGET_SAMPLE_RATE {
+        return Ok(mono);
+    }
+    let mut resampler = Resampler::new(spec.sample_rate, TARGET_SAMPLE_RATE);
+    let mut out = Vec::new();
+    resampler.process(&mono, &mut out);
+    resampler.finish(&mut out);
+    Ok(out)
+}
+
+/// The allowed language whisper.cpp finds most likely, or `None` if none of them is known.
+#[cfg(feature = "local-whisper")]
+fn detect_allowed(state: &mut WhisperState, samples: &[f32], allowed: &[String], threads: usize) -> Result<Option<String>, String> {
+    state.pcm_to_mel(samples, threads).map_err(|e| e.to_string())?;
+    let (_, probs) = state.lang_detect(0, threads).map_err(|e| format!("Language detection failed: {}", e))?;
+    let best = allowed.iter()
+        .filter_map(|code| {
+            let id = whisper_rs::get_lang_id(code)?;
+            Some((code, probs.get(id as usize).copied().unwrap_or(0.0)))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    Ok(best.map(|(code, _)| code.clone()))
+}
+
+// A word under construction: its bytes, start and end in 10 ms steps,
+// and the summed probability and count of its tokens
+#[cfg(feature = "local-whisper")]
+type PartialWord = (Vec<u8>, i64, i64, f32, u32);
+
+/// Word timing from whisper.cpp's token timestamps. Tokens are pieces of words;
+/// one starting with a space, or the first of a segment, begins a new word.
+#[cfg(feature = "local-whisper")]
+fn collect_words(state: &WhisperState, eot: WhisperTokenId) -> Result<Vec<Word>, String> {
+    let mut words = Vec::new();
+    let mut current: Option<PartialWord> = None;
+    for segment in state.as_iter() {
+        let mut segment_start = true;
+        for i in 0..segment.n_tokens() {
+            let Some(token) = segment.get_token(i) else { continue };
+            let data = token.token_data();
+            // Timestamps and other control tokens come after the text vocabulary
+            if data.id >= eot {
+                continue;
+            }
+            // Bytes, since a token may end inside a multi-byte character
+            let piece = token.to_bytes().map_err(|e| e.to_string())?;
+            let starts_word = segment_start || piece.first() == Some(&b' ');
+            segment_start = false;
+            match current.as_mut() {
+                Some((bytes, _, end, p, n)) if !starts_word => {
+                    bytes.extend_from_slice(piece);
+                    *end = data.t1;
+                    *p += data.p;
+                    *n += 1;
+                }
+                _ => {
+                    words.extend(current.take().and_then(finish_word));
+                    current = Some((piece.to_vec(), data.t0, data.t1, data.p, 1));
+                }
+            }
+        }
+    }
+    words.extend(current.and_then(finish_word));
+    Ok(words)
+}
+
+#[cfg(feature = "local-whisper")]
+fn finish_word((bytes, t0, t1, p, n): PartialWord) -> Option<Word> {
+    let word = String::from_utf8_lossy(&bytes).trim().to_string();
+    (!word.is_empty()).then(|| Word {
+        word,
+        start: t0 as f32 / 100.0,
+        end: t1 as f32 / 100.0,
+        confidence: Some(p / n.max(1) as f32),
+        speaker: None,
+    })
+}
+
+/// Transcribes with word timing and reports the language it was transcribed as.
+/// `prompt` primes the decoder with vocabulary it should prefer; raising
+/// `abort` stops the decode.
+#[cfg(feature = "local-whisper")]
+fn transcribe_wav(config: &LocalWhisperConfig, language: &LanguageConfig, prompt: Option<&str>, wav_data: &[u8], abort: &AtomicBool) -> Result<FullResponse, String> {
+    let ctx = load_model(Path::new(config.model_path.trim()))?;
+    let samples = wav_to_samples(wav_data)?;
+    if samples.is_empty() {
+        return Ok(FullResponse { text: String::new(), messages: Vec::new(), language: None, words: Some(Vec::new()) });
+    }
+
+    let mut state = ctx.create_state().map_err(|e| e.to_string())?;
+    let threads = config.thread_count();
+    let language = match language.fixed() {
+        Some(code) => Some(code),
+        None if !language.allowed().is_empty() => detect_allowed(&mut state, &samples, &language.allowed(), threads as usize)?,
+        None => None,
+    };
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_n_threads(threads);
+    params.set_language(Some(language.as_deref().unwrap_or("auto")));
+    if let Some(prompt) = prompt {
+        params.set_initial_prompt(prompt);
+    }
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+    params.set_token_timestamps(true);
+    // Polled between compute steps; `abort` outlives the call to `full`.
+    // The closure based setter of whisper-rs misreads captured state, so this takes a pointer.
+    unsafe {
+        params.set_abort_callback(Some(abort_requested));
+        params.set_abort_callback_user_data(abort as *const AtomicBool as *mut c_void);
+    }
+    let result = state.full(params, &samples);
+    if abort.load(Ordering::SeqCst) {
+        return Err("Local transcription was cancelled".to_string());
+    }
+    result.map_err(|e| format!("Whisper failed: {}", e))?;
+
+    let mut text = String::new();
+    for segment in state.as_iter() {
+        let part = segment.to_str_lossy().map_err(|e| e.to_string())?;
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text.push_str(part);
+    }
+    let words = collect_words(&state, ctx.token_eot())?;
+    let detected = whisper_rs::get_lang_str(state.full_lang_id_from_state()).map(str::to_string);
+    Ok(FullResponse { text, messages: Vec::new(), language: detected.or(language), words: Some(words) })
+}
+
+/// whisper.cpp running on this machine.
+#[cfg(feature = "local-whisper")]
+pub struct LocalWhisperBackend {
+    app: AppHandle,
+    config: LocalWhisperConfig,
+    language: LanguageConfig,
+    prompt: Option<String>,
+}
+
+#[cfg(feature = "local-whisper")]
+impl LocalWhisperBackend {
+    pub fn new(app: AppHandle, config: LocalWhisperConfig, language: LanguageConfig, prompt: Option<String>) -> Self {
+        Self { app, config, language, prompt }
+    }
+}
+
+#[cfg(feature = "local-whisper")]
+impl TranscriptionBackend for LocalWhisperBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn max_upload_bytes(&self) -> usize {
+        64 * 1024 * 1024 // ~30 minutes at 16 kHz; whisper.cpp windows long audio itself
+    }
+
+    fn transcribe(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> BackendFuture<'_> {
+        Box::pin(async move {
+            crate::write_to_log(&self.app, &format!("Local Whisper: transcribing chunk {}/{} ({} bytes, {} threads)",
+                chunk_idx, total, wav_data.len(), self.config.thread_count()));
+            let config = self.config.clone();
+            let language = self.language.clone();
+            let prompt = self.prompt.clone();
+            let abort = Arc::new(AtomicBool::new(false));
+            let _cancel = AbortOnDrop(abort.clone());
+            // Decoding is CPU bound and takes seconds; keep it off the async runtime
+            tauri::async_runtime::spawn_blocking(move || transcribe_wav(&config, &language, prompt.as_deref(), &wav_data, &abort))
+                .await
+                .map_err(|e| AppError::Other(e.to_string()))?
+                .map_err(AppError::Other)
+        })
+    }
+}
+
+/// Stands in for the local backend in builds without the `local-whisper` feature.
+#[cfg(not(feature = "local-whisper"))]
+pub struct UnavailableBackend;
+
+#[cfg(not(feature = "local-whisper"))]
+impl TranscriptionBackend for UnavailableBackend {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn max_upload_bytes(&self) -> usize {
+        usize::MAX
+    }
+
+    fn transcribe(&self, _wav_data: Vec<u8>, _chunk_idx: usize, _total: usize) -> BackendFuture<'_> {
+        Box::pin(async { Err(AppError::Other("This build has no local Whisper support".to_string())) })
+    }
+}
+
+#[cfg(all(test, feature = "local-whisper"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropping_a_transcription_aborts_only_its_own_decode() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let other = Arc::new(AtomicBool::new(false));
+        let _other_guard = AbortOnDrop(other.clone());
+        drop(AbortOnDrop(cancelled.clone()));
+        unsafe {
+            assert!(abort_requested(Arc::as_ptr(&cancelled) as *mut c_void));
+            assert!(!abort_requested(Arc::as_ptr(&other) as *mut c_void));
+        }
+    }
+}
@@ -0,0 +1,264 @@
+// Recordings that failed to transcribe, kept until a retry succeeds or the user
+// discards them. Each entry is `<id>.wav` plus `<id>.json` in data/outbox; the
+// metadata is written last, so a half-saved entry is never listed.
+
+use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use uuid::Uuid;
+use crate::spool::WavPayload;
+use crate::vad::TimeMap;
+
+// Automatic retries back off from this delay, doubling per failed attempt
+const RETRY_BASE_SECS: i64 = 30;
+const RETRY_MAX_SECS: i64 = 30 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboxEntry {
+    pub id: String,
+    /// When the recording was made.
+    pub timestamp: String,
+    pub duration: f32,
+    /// Error of the most recent attempt.
+    pub error: String,
+    /// Transcription attempts so far, including the original one.
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_attempt: Option<String>,
+    /// Kept from a cancelled dictation; only retried on request.
+    #[serde(default)]
+    pub cancelled: bool,
+    /// Silence was trimmed from the audio; word times are moved back with this.
+    #[serde(default)]
+    pub time_map: TimeMap,
+}
+
+impl OutboxEntry {
+    /// Whether the automatic retry backoff for this entry has run out.
+    pub fn is_due(&self) -> bool {
+        if self.cancelled {
+            return false;
+        }
+        let Some(last) = self.last_attempt.as_deref().and_then(|t| DateTime::parse_from_rfc3339(t).ok()) else {
+            return true;
+        };
+        let backoff = RETRY_BASE_SECS
+            .saturating_mul(1 << self.attempts.saturating_sub(1).min(16))
+            .min(RETRY_MAX_SECS);
+        Local::now().signed_duration_since(last).num_seconds() >= backoff
+    }
+}
+
+pub fn get_outbox_dir() -> PathBuf {
+    let appdata = std::env::var("LOCALAPPDATA").unwrap_or_else(|_| ".".to_string());
+    let dir = std::path::Path::new(&appdata).join("Voice2Text").join("data").join("outbox");
+    let _ = std::fs::create_dir_all(&dir);
+    dir
+}
+
+/// Rejects ids that could point outside the outbox folder.
+fn entry_paths(id: &str) -> Result<(PathBuf, PathBuf), String> {
+    Uuid::parse_str(id).map_err(|_| format!("Invalid outbox id: {}", id))?;
+    let dir = get_outbox_dir();
+    Ok((dir.join(format!("{}.wav", id)), dir.join(format!("{}.json", id))))
+}
+
+fn write_entry(entry: &OutboxEntry) -> Result<(), String> {
+    let (_, meta) = entry_paths(&entry.id)?;
+    let json = serde_json::to_string_pretty(entry).map_err(|e| e.to_string())?;
+    std::fs::write(meta, json).map_err(|e| e.to_string())
+}
+
+/// Keeps a recording whose transcription failed with `error`.
+pub fn save(wav: &WavPayload, time_map: &TimeMap, duration: f32, error: &str) -> Result<OutboxEntry, String> {
+    store(wav, time_map, duration, error, false)
+}
+
+/// Keeps the recording of a cancelled dictation until the user retries or discards it.
+pub fn save_cancelled(wav: &WavPayload, time_map: &TimeMap, duration: f32) -> Result<OutboxEntry, String> {
+    store(wav, time_map, duration, "Cancelled", true)
+}
+
+fn store(wav: &WavPayload, time_map: &TimeMap, duration: f32, error: &str, cancelled: bool) -> Result<OutboxEntry, String> {
+    let now = Local::now().to_rfc3339();
+    let entry = OutboxEntry {
+        id: Uuid::new_v4().to_string(),
+        timestamp: now.clone(),
+        duration,
+        error: error.to_string(),
+        attempts: 1,
+        last_attempt: Some(now),
+        cancelled,
+        time_map: time_map.clone(),
+    };
+    let (audio, _) = entry_paths(&entry.id)?;
+    wav.write_to(&audio)?;
+    write_entry(&entry)?;
+    Ok(entry)
+}
+
+/// All entries, oldest first.
+pub fn list() -> Vec<OutboxEntry> {
+    let Ok(dir) = std::fs::read_dir(get_outbox_dir()) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<OutboxEntry> = dir
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| std::fs::read_to_string(p).ok())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+    entries.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    entries
+}
+
+pub fn get(id: &str) -> Result<OutboxEntry, String> {
+    let (_, meta) = entry_paths(id)?;
+    let json = std::fs::read_to_string(meta).map_err(|_| format!("No outbox entry {}", id))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// The entry's recording, read from the outbox file as it is needed; hold
+/// a `Claim` so it isn't removed meanwhile.
+pub fn load_wav(id: &str) -> Result<WavPayload, String> {
+    let (audio, _) = entry_paths(id)?;
+    if !audio.is_file() {
+        return Err(format!("Recording of outbox entry {} is missing", id));
+    }
+    Ok(WavPayload::Stored(audio))
+}
+
+/// Notes another failed attempt on the entry.
+pub fn record_failure(id: &str, error: &str) -> Result<(), String> {
+    let mut entry = get(id)?;
+    entry.error = error.to_string();
+    entry.attempts += 1;
+    entry.last_attempt = Some(Local::now().to_rfc3339());
+    // Asked for once, it is retried like any other failed upload
+    entry.cancelled = false;
+    write_entry(&entry)
+}
+
+pub fn remove(id: &str) -> Result<(), String> {
+    let (audio, meta) = entry_paths(id)?;
+    // Metadata first, so a partly removed entry disappears from the list
+    std::fs::remove_file(meta).map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(audio);
+    Ok(())
+}
+
+// Entries being retried right now, so manual and automatic retries never overlap
+static IN_FLIGHT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Exclusive hold on one entry while it is retried or discarded.
+pub struct Claim(String);
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().retain(|id| id != &self.0);
+    }
+}
+
+/// `None` while another retry of the same entry is running.
+pub fn claim(id: &str) -> Option<Claim> {
+    let mut in_flight = IN_FLIGHT.lock().unwrap();
+    if in_flight.iter().any(|i| i == id) {
+        return None;
+    }
+    in_flight.push(id.to_string());
+    Some(Claim(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(attempts: u32, secs_ago: i64) -> OutboxEntry {
+        let last = Local::now() - chrono::Duration::seconds(secs_ago);
+        OutboxEntry {
+            id: Uuid::new_v4().to_string(),
+            timestamp: last.to_rfc3339(),
+            duration: 1.0,
+            error: "Network error".to_string(),
+            attempts,
+            last_attempt: Some(last.to_rfc3339()),
+            cancelled: false,
+            time_map: TimeMap::default(),
+        }
+    }
+
+    #[test]
+    fn retry_backoff_doubles_from_30_seconds_up_to_30_minutes() {
+        for (attempts, wait) in [(1, 30), (2, 60), (3, 120), (6, 960), (7, 1800), (40, 1800)] {
+            assert!(!entry(attempts, wait - 2).is_due(), "{} attempts, {}s", attempts, wait - 2);
+            assert!(entry(attempts, wait + 2).is_due(), "{} attempts, {}s", attempts, wait + 2);
+        }
+    }
+
+    #[test]
+    fn cancelled_entries_are_never_due_and_unknown_times_always() {
+        let mut cancelled = entry(1, 3600);
+        cancelled.cancelled = true;
+        assert!(!cancelled.is_due());
+
+        let mut never_tried = entry(3, 0);
+        never_tried.last_attempt = None;
+        assert!(never_tried.is_due());
+        never_tried.last_attempt = Some("yesterday".to_string());
+        assert!(never_tried.is_due());
+    }
+
+    #[test]
+    fn ids_must_be_uuids() {
+        for id in ["", "../settings", "..\\..\\settings", "C:\\Windows\\win", "/etc/passwd", "abc.json"] {
+            assert!(entry_paths(id).is_err(), "{}", id);
+            assert!(get(id).is_err() && load_wav(id).is_err() && remove(id).is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn a_claim_is_exclusive_until_dropped() {
+        let id = Uuid::new_v4().to_string();
+        let other = Uuid::new_v4().to_string();
+        let held = claim(&id).expect("free entry");
+        assert!(claim(&id).is_none());
+        let other_claim = claim(&other).expect("other entries are unaffected");
+        drop(held);
+        assert!(claim(&id).is_some());
+        drop(other_claim);
+    }
+
+    #[test]
+    fn saved_recordings_are_read_from_the_outbox_file() {
+        let appdata = tempfile::tempdir().unwrap();
+        std::env::set_var("LOCALAPPDATA", appdata.path());
+
+        let bytes = {
+            let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+            let mut bytes = Vec::new();
+            let mut writer = hound::WavWriter::new(std::io::Cursor::new(&mut bytes), spec).unwrap();
+            for i in 0..1600 {
+                writer.write_sample(i as i16).unwrap();
+            }
+            writer.finalize().unwrap();
+            bytes
+        };
+        let saved = save(&WavPayload::Memory(bytes.clone()), &TimeMap::default(), 0.1, "Network error").unwrap();
+        assert_eq!(get(&saved.id).unwrap().attempts, 1);
+
+        let wav = load_wav(&saved.id).unwrap();
+        let WavPayload::Stored(path) = &wav else { panic!("loaded into memory") };
+        assert!(path.starts_with(appdata.path()));
+        assert_eq!(wav.len(), bytes.len());
+        assert_eq!(wav.to_bytes().unwrap(), bytes);
+        // Dropping the payload leaves the entry's file alone
+        drop(wav);
+        assert!(load_wav(&saved.id).is_ok());
+
+        remove(&saved.id).unwrap();
+        assert!(load_wav(&saved.id).is_err());
+        assert!(list().is_empty());
+    }
+}
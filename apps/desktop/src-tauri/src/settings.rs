@@ -0,0 +1,83 @@
+use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+
+// Accepted pre-roll range for the warm mic
+const MIN_PREROLL_MS: u32 = 100;
+const MAX_PREROLL_MS: u32 = 2000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Selected input device id (or a device name saved by older versions). `None` is the system default.
+    pub input_device: Option<String>,
+    pub vad: crate::vad::VadConfig,
+    pub warm_mic: WarmMicConfig,
+    /// Keep recording on the system default device when the selected one disappears.
+    pub device_failover: bool,
+    pub transcription: crate::transcribe::TranscriptionConfig,
+    /// Service address, proxy and certificates.
+    pub api: crate::api::ApiConfig,
+    /// Move cancelled dictations to the outbox instead of discarding them.
+    pub keep_cancelled: bool,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            input_device: None,
+            vad: crate::vad::VadConfig::default(),
+            warm_mic: WarmMicConfig::default(),
+            device_failover: true,
+            transcription: crate::transcribe::TranscriptionConfig::default(),
+            api: crate::api::ApiConfig::default(),
+            keep_cancelled: false,
+        }
+    }
+}
+
+/// Keeps the microphone open between recordings so speech that starts
+/// together with the hotkey isn't cut off. Off by default for privacy:
+/// the buffered audio never leaves memory unless a recording starts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WarmMicConfig {
+    pub enabled: bool,
+    pub preroll_ms: u32,
+}
+
+impl Default for WarmMicConfig {
+    fn default() -> Self {
+        Self { enabled: false, preroll_ms: 500 }
+    }
+}
+
+impl WarmMicConfig {
+    pub fn clamped_preroll_ms(&self) -> u32 {
+        self.preroll_ms.clamp(MIN_PREROLL_MS, MAX_PREROLL_MS)
+    }
+}
+
+pub fn get_settings_path() -> PathBuf {
+    let appdata = std::env::var("LOCALAPPDATA").unwrap_or_else(|_| ".".to_string());
+    let dir = std::path::Path::new(&appdata).join("Voice2Text").join("data");
+    let _ = std::fs::create_dir_all(&dir);
+    dir.join("settings.json")
+}
+
+/// Loads saved settings, falling back to defaults for a missing or unreadable file.
+/// The API key is filled in from the credential store.
+pub fn load_settings() -> AppSettings {
+    let mut settings: AppSettings = std::fs::read_to_string(get_settings_path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+
+    settings.transcription.openai.api_key = crate::credentials::load(crate::credentials::OPENAI_API_KEY).unwrap_or_default();
+    settings.transcription.openai.api_key_set = !settings.transcription.openai.api_key.is_empty();
+    settings
+}
+
+pub fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    std::fs::write(get_settings_path(), json).map_err(|e| e.to_string())
+}
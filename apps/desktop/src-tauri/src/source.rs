@@ -0,0 +1,417 @@
+// Audio sources for the recorder.
+// Live capture comes from cpal (see audio.rs); WAV/FLAC files and a
+// synthetic generator feed the same pipeline without any hardware, for
+// headless runs and for replaying a file as if it were spoken.
+
+use ringbuf::traits::Producer;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// Frames per block delivered by file and synthetic sources (10 ms at 48 kHz)
+const SOURCE_BLOCK_FRAMES: usize = 480;
+// Back-off while the ring is full and the source isn't paced in real time
+const RING_FULL_WAIT: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// A problem reported by a source while it is open.
+#[derive(Clone, Debug)]
+pub struct StreamFailure {
+    /// Identifies the stream that failed; see `AudioRecorder::is_current_stream`.
+    pub generation: u64,
+    pub message: String,
+    /// The stream is gone (device unplugged, invalidated) rather than glitching.
+    pub fatal: bool,
+}
+
+/// Sends a source's failures to the recorder's error channel.
+#[derive(Clone)]
+pub struct FailureReporter {
+    tx: Option<Sender<StreamFailure>>,
+    generation: u64,
+}
+
+impl FailureReporter {
+    pub fn report(&self, message: String, fatal: bool) {
+        eprintln!("Audio error: {}", message);
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(StreamFailure { generation: self.generation, message, fatal });
+        }
+    }
+}
+
+/// Where a source delivers interleaved f32 samples in [-1.0, 1.0].
+pub struct SampleSink {
+    producer: ringbuf::HeapProd<f32>,
+    dropped: Arc<AtomicUsize>,
+    failures: FailureReporter,
+}
+
+impl SampleSink {
+    pub fn new(producer: ringbuf::HeapProd<f32>, dropped: Arc<AtomicUsize>, errors: Option<Sender<StreamFailure>>, generation: u64) -> Self {
+        Self { producer, dropped, failures: FailureReporter { tx: errors, generation } }
+    }
+
+    pub fn failures(&self) -> FailureReporter {
+        self.failures.clone()
+    }
+
+    /// Pushes without ever blocking; samples that don't fit are counted as dropped.
+    pub fn push(&mut self, samples: &[f32]) {
+        let pushed = self.producer.push_slice(samples);
+        if pushed < samples.len() {
+            self.dropped.fetch_add(samples.len() - pushed, Ordering::Relaxed);
+        }
+    }
+
+    /// Pushes everything, waiting for the worker to make room.
+    /// Returns false if `stop` was raised first.
+    fn push_all(&mut self, mut samples: &[f32], stop: &AtomicBool) -> bool {
+        while !samples.is_empty() {
+            if stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            let n = self.producer.push_slice(samples);
+            samples = &samples[n..];
+            if n == 0 {
+                std::thread::sleep(RING_FULL_WAIT);
+            }
+        }
+        true
+    }
+}
+
+/// Something the recorder can capture from.
+pub trait AudioSource {
+    /// Label for logs.
+    fn name(&self) -> String;
+    fn format(&self) -> SourceFormat;
+    /// Prepares delivery into `sink`. Nothing is delivered before `play`.
+    fn open(self: Box<Self>, sink: SampleSink) -> Result<Box<dyn SourceStream>, String>;
+}
+
+/// An opened source. Delivery stops when it is dropped.
+pub trait SourceStream: Send {
+    fn play(&mut self) -> Result<(), String>;
+    /// Called when a recording stops. Sources that aren't paced in real time
+    /// block here until they have delivered everything, so a headless run
+    /// always captures the whole input.
+    fn finish(&mut self) {}
+}
+
+/// Delivers blocks from a producer thread, either at the source's own pace
+/// or as fast as the recorder takes them.
+struct Pacer {
+    sink: SampleSink,
+    stop: Arc<AtomicBool>,
+    realtime: bool,
+    sample_rate: u32,
+    block: Vec<f32>,
+    block_len: usize,
+    frames: u64,
+    channels: usize,
+    started: Instant,
+}
+
+impl Pacer {
+    /// Queues one sample; returns false once the stream has been dropped.
+    fn feed(&mut self, sample: f32) -> bool {
+        self.block.push(sample);
+        self.block.len() < self.block_len || self.flush()
+    }
+
+    fn flush(&mut self) -> bool {
+        if self.block.is_empty() {
+            return true;
+        }
+        let delivered = if self.realtime {
+            self.sink.push(&self.block);
+            true
+        } else {
+            self.sink.push_all(&self.block, &self.stop)
+        };
+        self.frames += (self.block.len() / self.channels) as u64;
+        self.block.clear();
+
+        if self.realtime {
+            let due = Duration::from_secs_f64(self.frames as f64 / self.sample_rate as f64);
+            if let Some(wait) = due.checked_sub(self.started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        delivered && !self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// Producer thread of a file or synthetic source.
+struct ThreadStream {
+    start: Option<Sender<()>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    realtime: bool,
+}
+
+impl ThreadStream {
+    /// Spawns `produce`, which runs once `play` is called and feeds samples through the pacer.
+    fn spawn<F>(sink: SampleSink, format: SourceFormat, realtime: bool, produce: F) -> Self
+    where
+        F: FnOnce(&mut Pacer) -> Result<(), String> + Send + 'static,
+    {
+        let (start, start_rx) = mpsc::channel::<()>();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            if start_rx.recv().is_err() {
+                return;
+            }
+            let channels = format.channels.max(1) as usize;
+            let failures = sink.failures();
+            let mut pacer = Pacer {
+                sink,
+                stop: thread_stop,
+                realtime,
+                sample_rate: format.sample_rate.max(1),
+                block: Vec::with_capacity(SOURCE_BLOCK_FRAMES * channels),
+                block_len: SOURCE_BLOCK_FRAMES * channels,
+                frames: 0,
+                channels,
+                started: Instant::now(),
+            };
+            let result = produce(&mut pacer);
+            pacer.flush();
+            // A broken file ends the input early but isn't a lost device
+            if let Err(e) = result {
+                failures.report(format!("Source failed: {}", e), false);
+            }
+        });
+        Self { start: Some(start), stop, thread: Some(thread), realtime }
+    }
+}
+
+impl SourceStream for ThreadStream {
+    fn play(&mut self) -> Result<(), String> {
+        match self.start.take() {
+            Some(start) => start.send(()).map_err(|_| "Source thread stopped".to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.realtime || self.start.is_some() {
+            return;
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ThreadStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+enum Decoder {
+    Wav(hound::WavReader<BufReader<File>>),
+    Flac(claxon::FlacReader<File>),
+}
+
+/// A WAV or FLAC file played into the recorder.
+pub struct FileSource {
+    path: PathBuf,
+    decoder: Decoder,
+    format: SourceFormat,
+    realtime: bool,
+}
+
+impl FileSource {
+    /// Opens a WAV or FLAC file (chosen by extension). With `realtime` the
+    /// samples arrive at the file's own pace, as if spoken into a microphone;
+    /// otherwise as fast as the recorder takes them.
+    pub fn open(path: impl AsRef<Path>, realtime: bool) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let is_flac = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("flac"));
+        let (decoder, format) = if is_flac {
+            let reader = claxon::FlacReader::open(&path).map_err(|e| e.to_string())?;
+            let info = reader.streaminfo();
+            let format = SourceFormat { sample_rate: info.sample_rate, channels: info.channels as u16 };
+            (Decoder::Flac(reader), format)
+        } else {
+            let reader = hound::WavReader::open(&path).map_err(|e| e.to_string())?;
+            let spec = reader.spec();
+            let format = SourceFormat { sample_rate: spec.sample_rate, channels: spec.channels };
+            (Decoder::Wav(reader), format)
+        };
+        if format.sample_rate == 0 || format.channels == 0 {
+            return Err(format!("Invalid audio format in {}", path.display()));
+        }
+        Ok(Self { path, decoder, format, realtime })
+    }
+}
+
+impl AudioSource for FileSource {
+    fn name(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    fn open(self: Box<Self>, sink: SampleSink) -> Result<Box<dyn SourceStream>, String> {
+        let decoder = self.decoder;
+        let stream = ThreadStream::spawn(sink, self.format, self.realtime, move |pacer| match decoder {
+            Decoder::Wav(mut reader) => {
+                let spec = reader.spec();
+                match spec.sample_format {
+                    hound::SampleFormat::Float => {
+                        for sample in reader.samples::<f32>() {
+                            if !pacer.feed(sample.map_err(|e| e.to_string())?) {
+                                break;
+                            }
+                        }
+                    }
+                    hound::SampleFormat::Int => {
+                        let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+                        for sample in reader.samples::<i32>() {
+                            if !pacer.feed(sample.map_err(|e| e.to_string())? as f32 / scale) {
+                                break;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Decoder::Flac(mut reader) => {
+                let scale = (1u64 << (reader.streaminfo().bits_per_sample - 1)) as f32;
+                for sample in reader.samples() {
+                    if !pacer.feed(sample.map_err(|e| e.to_string())? as f32 / scale) {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        });
+        Ok(Box::new(stream))
+    }
+}
+
+/// One step of a synthetic signal.
+#[derive(Clone, Debug)]
+pub enum Signal {
+    Silence { ms: u32 },
+    Tone { freq: f32, amplitude: f32, ms: u32 },
+    Noise { amplitude: f32, ms: u32 },
+}
+
+/// Programmable test signal: a sequence of tones, noise and silence,
+/// identical on every channel, delivered as fast as the recorder takes it.
+pub struct SyntheticSource {
+    format: SourceFormat,
+    signals: Vec<Signal>,
+}
+
+impl SyntheticSource {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self { format: SourceFormat { sample_rate, channels }, signals: Vec::new() }
+    }
+
+    pub fn silence(mut self, ms: u32) -> Self {
+        self.signals.push(Signal::Silence { ms });
+        self
+    }
+
+    pub fn tone(mut self, freq: f32, amplitude: f32, ms: u32) -> Self {
+        self.signals.push(Signal::Tone { freq, amplitude, ms });
+        self
+    }
+
+    pub fn noise(mut self, amplitude: f32, ms: u32) -> Self {
+        self.signals.push(Signal::Noise { amplitude, ms });
+        self
+    }
+
+    /// Parses a comma separated list of steps played at 48 kHz mono, e.g.
+    /// `noise:0.001:1000,tone:220:0.3:3000,silence:500` (amplitudes in full
+    /// scale, durations in ms).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut source = Self::new(48000, 1);
+        for step in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let parts: Vec<&str> = step.split(':').collect();
+            let num = |i: usize| -> Result<f32, String> {
+                parts.get(i).and_then(|p| p.trim().parse().ok()).ok_or_else(|| format!("Invalid synthetic step '{}'", step))
+            };
+            source = match (parts[0], parts.len()) {
+                ("silence", 2) => source.silence(num(1)? as u32),
+                ("tone", 4) => source.tone(num(1)?, num(2)?, num(3)? as u32),
+                ("noise", 3) => source.noise(num(1)?, num(2)? as u32),
+                _ => return Err(format!("Invalid synthetic step '{}'", step)),
+            };
+        }
+        if source.signals.is_empty() {
+            return Err("Empty synthetic signal".to_string());
+        }
+        Ok(source)
+    }
+}
+
+impl AudioSource for SyntheticSource {
+    fn name(&self) -> String {
+        format!("synthetic ({} steps)", self.signals.len())
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format
+    }
+
+    fn open(self: Box<Self>, sink: SampleSink) -> Result<Box<dyn SourceStream>, String> {
+        let SourceFormat { sample_rate, channels } = self.format;
+        if sample_rate == 0 || channels == 0 {
+            return Err("Invalid synthetic format".to_string());
+        }
+        let signals = self.signals;
+        let stream = ThreadStream::spawn(sink, self.format, false, move |pacer| {
+            // xorshift32; deterministic so runs are repeatable
+            let mut seed = 0x9E37_79B9u32;
+            for signal in signals {
+                let (ms, amplitude) = match signal {
+                    Signal::Silence { ms } => (ms, 0.0),
+                    Signal::Tone { amplitude, ms, .. } | Signal::Noise { amplitude, ms } => (ms, amplitude),
+                };
+                let frames = sample_rate as u64 * ms as u64 / 1000;
+                for n in 0..frames {
+                    let value = match signal {
+                        Signal::Silence { .. } => 0.0,
+                        Signal::Tone { freq, .. } => {
+                            amplitude * (2.0 * std::f64::consts::PI * freq as f64 * n as f64 / sample_rate as f64).sin() as f32
+                        }
+                        Signal::Noise { .. } => {
+                            seed ^= seed << 13;
+                            seed ^= seed >> 17;
+                            seed ^= seed << 5;
+                            amplitude * (seed as f32 / u32::MAX as f32 * 2.0 - 1.0)
+                        }
+                    };
+                    for _ in 0..channels {
+                        if !pacer.feed(value) {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Ok(())
+        });
+        Ok(Box::new(stream))
+    }
+}
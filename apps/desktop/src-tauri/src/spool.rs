@@ -0,0 +1,194 @@
+// Disk-backed storage for long recordings.
+// Samples stay in memory until a recording outgrows the spill threshold,
+// after which everything is streamed to a temporary file.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::path::PathBuf;
+use tempfile::TempPath;
+
+/// f32 samples kept in memory before a recording spills to disk (16 MB).
+pub const SPILL_THRESHOLD: usize = 4 * 1024 * 1024;
+
+// Samples per block when streaming a spilled recording back
+const READ_BLOCK: usize = 16 * 1024;
+
+/// Append-only f32 sample store for one recording.
+pub struct SampleSpool {
+    memory: Vec<f32>,
+    file: Option<(BufWriter<File>, TempPath)>,
+    threshold: usize,
+}
+
+impl SampleSpool {
+    pub fn new(threshold: usize) -> Self {
+        Self { memory: Vec::new(), file: None, threshold }
+    }
+
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn push_slice(&mut self, samples: &[f32]) -> Result<(), String> {
+        if self.file.is_none() && self.memory.len() + samples.len() > self.threshold {
+            let (file, path) = temp_file("v2t-capture-", ".f32")?;
+            let mut writer = BufWriter::new(file);
+            write_f32(&mut writer, &self.memory)?;
+            self.memory = Vec::new();
+            self.file = Some((writer, path));
+        }
+
+        match self.file.as_mut() {
+            Some((writer, _)) => write_f32(writer, samples)?,
+            None => self.memory.extend_from_slice(samples),
+        }
+        Ok(())
+    }
+
+    /// Streams the stored samples back in order, block by block.
+    pub fn for_each_block(mut self, mut f: impl FnMut(&[f32]) -> Result<(), String>) -> Result<(), String> {
+        let Some((mut writer, path)) = self.file.take() else {
+            for block in self.memory.chunks(READ_BLOCK) {
+                f(block)?;
+            }
+            return Ok(());
+        };
+
+        writer.flush().map_err(|e| e.to_string())?;
+        drop(writer);
+        let mut reader = BufReader::new(File::open(&path).map_err(|e| e.to_string())?);
+        let mut bytes = vec![0u8; READ_BLOCK * 4];
+        let mut block = Vec::with_capacity(READ_BLOCK);
+        loop {
+            let n = read_full(&mut reader, &mut bytes)?;
+            if n == 0 {
+                break;
+            }
+            block.clear();
+            block.extend(bytes[..n - n % 4].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+            f(&block)?;
+        }
+        Ok(())
+    }
+}
+
+/// A finished 16-bit WAV, in memory for short takes or in a temp file that
+/// is removed when the payload is dropped.
+pub enum WavPayload {
+    Memory(Vec<u8>),
+    File(TempPath),
+    /// A file kept by someone else, e.g. an outbox entry; left in place.
+    Stored(PathBuf),
+}
+
+impl WavPayload {
+    /// Size of the encoded WAV in bytes.
+    pub fn len(&self) -> usize {
+        match self {
+            WavPayload::Memory(bytes) => bytes.len(),
+            WavPayload::File(path) => std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0),
+            WavPayload::Stored(path) => std::fs::metadata(path).map(|m| m.len() as usize).unwrap_or(0),
+        }
+    }
+
+    pub fn reader(&self) -> Result<hound::WavReader<Box<dyn Read + Send + '_>>, String> {
+        let source: Box<dyn Read + Send + '_> = match self {
+            WavPayload::Memory(bytes) => Box::new(Cursor::new(bytes.as_slice())),
+            WavPayload::File(path) => Box::new(BufReader::new(File::open(path).map_err(|e| e.to_string())?)),
+            WavPayload::Stored(path) => Box::new(BufReader::new(File::open(path).map_err(|e| e.to_string())?)),
+        };
+        hound::WavReader::new(source).map_err(|e| e.to_string())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            WavPayload::Memory(bytes) => Ok(bytes.clone()),
+            WavPayload::File(path) => std::fs::read(path).map_err(|e| e.to_string()),
+            WavPayload::Stored(path) => std::fs::read(path).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn write_to(&self, dest: &std::path::Path) -> Result<(), String> {
+        match self {
+            WavPayload::Memory(bytes) => std::fs::write(dest, bytes).map_err(|e| e.to_string()),
+            WavPayload::File(path) => std::fs::copy(path, dest).map(|_| ()).map_err(|e| e.to_string()),
+            WavPayload::Stored(path) => std::fs::copy(path, dest).map(|_| ()).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Destination for an encoded WAV: memory, or a temp file for large outputs.
+pub enum WavSink {
+    Memory(Vec<i16>, hound::WavSpec),
+    File(hound::WavWriter<BufWriter<File>>, TempPath),
+}
+
+impl WavSink {
+    pub fn new(spec: hound::WavSpec, on_disk: bool) -> Result<Self, String> {
+        if on_disk {
+            let (file, path) = temp_file("v2t-", ".wav")?;
+            let writer = hound::WavWriter::new(BufWriter::new(file), spec).map_err(|e| e.to_string())?;
+            Ok(WavSink::File(writer, path))
+        } else {
+            Ok(WavSink::Memory(Vec::new(), spec))
+        }
+    }
+
+    pub fn write_sample(&mut self, sample: i16) -> Result<(), String> {
+        match self {
+            WavSink::Memory(samples, _) => {
+                samples.push(sample);
+                Ok(())
+            }
+            WavSink::File(w, _) => w.write_sample(sample).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn finish(self) -> Result<WavPayload, String> {
+        match self {
+            WavSink::Memory(samples, spec) => {
+                let mut wav_data = Vec::new();
+                let mut writer = hound::WavWriter::new(Cursor::new(&mut wav_data), spec).map_err(|e| e.to_string())?;
+                for s in samples {
+                    writer.write_sample(s).map_err(|e| e.to_string())?;
+                }
+                writer.finalize().map_err(|e| e.to_string())?;
+                Ok(WavPayload::Memory(wav_data))
+            }
+            WavSink::File(w, path) => {
+                w.finalize().map_err(|e| e.to_string())?;
+                Ok(WavPayload::File(path))
+            }
+        }
+    }
+}
+
+fn temp_file(prefix: &str, suffix: &str) -> Result<(File, TempPath), String> {
+    let file = tempfile::Builder::new()
+        .prefix(prefix)
+        .suffix(suffix)
+        .tempfile()
+        .map_err(|e| e.to_string())?;
+    Ok(file.into_parts())
+}
+
+fn write_f32(writer: &mut impl Write, samples: &[f32]) -> Result<(), String> {
+    for s in samples {
+        writer.write_all(&s.to_le_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Fills `buf` as far as the reader allows; returns fewer bytes only at EOF.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(filled)
+}
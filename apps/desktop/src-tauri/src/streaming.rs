@@ -0,0 +1,424 @@
+// Streaming transcription over WebSocket.
+// Audio is sent while the user speaks, partial results come back as they are
+// recognised, and the final text is ready almost as soon as the key is released.
+//
+// Protocol (JSON in text frames, audio in binary frames):
+//   client -> {"type":"start","sample_rate":16000,"channels":1,"encoding":"pcm_s16le",
+//              "language":"de"|null,"allowed_languages":["de","en"],
+//              "vocabulary":[{"term":"Voice2Text","boost":2.0}]}
+//   client -> binary frames of 16-bit little-endian PCM
+//   client -> {"type":"stop"}                    after the recording ended
+//   server -> {"type":"partial","text":"..."}    interim hypothesis for the current segment
+//   server -> {"type":"final","text":"..."}      finished segment, appended to the transcript;
+//              may carry "words":[{"word","start","end","confidence","speaker"}] timed from the stream start
+//   server -> {"type":"done","messages":[...],"language":"de"}   all audio processed
+//   server -> {"type":"error","message":"..."}
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Serialize, Deserialize};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use crate::audio::{CapturedFrames, Resampler, TARGET_SAMPLE_RATE};
+use crate::transcribe::{FullResponse, LanguageConfig, Word};
+use crate::vocabulary::VocabularyTerm;
+
+// How long the server gets to deliver the final result after release
+const FINISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct StreamingConfig {
+    pub enabled: bool,
+    /// WebSocket endpoint; empty uses the Voice2Text service.
+    pub url: String,
+}
+
+impl StreamingConfig {
+    pub fn endpoint(&self) -> String {
+        if !self.url.trim().is_empty() {
+            return self.url.trim().to_string();
+        }
+        crate::api::ws_url("/api/transcribe/stream")
+    }
+}
+
+/// Payload of the `transcription-partial` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct TranscriptPartial {
+    /// Finished segments plus the current interim hypothesis.
+    pub text: String,
+    /// `text` holds finished segments only.
+    pub is_final: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerEvent {
+    Partial { text: String },
+    Final {
+        text: String,
+        #[serde(default)]
+        words: Option<Vec<Word>>,
+    },
+    Done {
+        #[serde(default)]
+        messages: Vec<crate::auth::ServerMessage>,
+        #[serde(default)]
+        language: Option<String>,
+    },
+    Error { message: String },
+}
+
+/// A live streaming transcription tied to one recording.
+pub struct StreamSession {
+    task: JoinHandle<Result<FullResponse, String>>,
+}
+
+impl StreamSession {
+    /// Connects to `url` and streams `frames` until the recording stops and the
+    /// channel closes. Must be called from within the async runtime.
+    pub fn start<F>(
+        url: String,
+        token: String,
+        language: LanguageConfig,
+        vocabulary: Vec<VocabularyTerm>,
+        frames: Receiver<CapturedFrames>,
+        on_partial: F,
+    ) -> Self
+    where
+        F: Fn(TranscriptPartial) + Send + 'static,
+    {
+        let (audio_tx, audio_rx) = unbounded_channel();
+        std::thread::spawn(move || encode_frames(frames, audio_tx));
+        let task = tokio::spawn(run_session(url, token, language, vocabulary, audio_rx, on_partial));
+        Self { task }
+    }
+
+    /// Waits for the final transcript once the recording has stopped.
+    pub async fn finish(mut self) -> Result<FullResponse, String> {
+        match tokio::time::timeout(FINISH_TIMEOUT, &mut self.task).await {
+            Ok(joined) => joined.map_err(|e| e.to_string())?,
+            Err(_) => {
+                self.task.abort();
+                Err("Streaming transcription timed out".to_string())
+            }
+        }
+    }
+
+    /// Drops the connection without waiting for a result.
+    pub fn cancel(self) {
+        self.task.abort();
+    }
+}
+
+// A session nobody waits for any more, e.g. of a cancelled dictation, closes its connection
+impl Drop for StreamSession {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Downmixes and resamples captured blocks to 16 kHz mono PCM16 for the wire.
+/// Dropping `audio_tx` at the end tells the session that the recording is over.
+fn encode_frames(frames: Receiver<CapturedFrames>, audio_tx: UnboundedSender<Vec<u8>>) {
+    let mut format: Option<(u32, u16)> = None;
+    let mut resampler = Resampler::new(TARGET_SAMPLE_RATE, TARGET_SAMPLE_RATE);
+    let mut carry: Vec<f32> = Vec::new();
+    let mut mono = Vec::new();
+    let mut out = Vec::new();
+
+    for block in frames {
+        // The format changes when a lost device is replaced mid-recording
+        if format != Some((block.sample_rate, block.channels)) {
+            if format.is_some() {
+                out.clear();
+                resampler.finish(&mut out);
+                let _ = audio_tx.send(to_pcm16(&out));
+            }
+            format = Some((block.sample_rate, block.channels));
+            resampler = Resampler::new(block.sample_rate, TARGET_SAMPLE_RATE);
+            carry.clear();
+        }
+
+        let channels = block.channels.max(1) as usize;
+        carry.extend_from_slice(&block.samples);
+        let whole = carry.len() - carry.len() % channels;
+        mono.clear();
+        mono.extend(carry[..whole].chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32));
+        carry.drain(..whole);
+
+        out.clear();
+        resampler.process(&mono, &mut out);
+        // If the session already ended, keep draining so the recorder never notices
+        let _ = audio_tx.send(to_pcm16(&out));
+    }
+    out.clear();
+    resampler.finish(&mut out);
+    let _ = audio_tx.send(to_pcm16(&out));
+}
+
+fn to_pcm16(samples: &[f32]) -> Vec<u8> {
+    samples.iter()
+        .flat_map(|&s| ((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+        .collect()
+}
+
+fn append_segment(transcript: &mut String, text: &str) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    if !transcript.is_empty() {
+        transcript.push(' ');
+    }
+    transcript.push_str(text);
+}
+
+async fn run_session<F>(
+    url: String,
+    token: String,
+    language: LanguageConfig,
+    vocabulary: Vec<VocabularyTerm>,
+    mut audio_rx: UnboundedReceiver<Vec<u8>>,
+    on_partial: F,
+) -> Result<FullResponse, String>
+where
+    F: Fn(TranscriptPartial),
+{
+    let mut request = url.as_str().into_client_request().map_err(|e| e.to_string())?;
+    let auth = format!("Bearer {}", token).parse().map_err(|_| "Invalid token".to_string())?;
+    request.headers_mut().insert("Authorization", auth);
+    let socket = crate::api::connect_websocket(request).await?;
+    let (mut write, mut read) = socket.split();
+
+    let start = serde_json::json!({
+        "type": "start",
+        "sample_rate": TARGET_SAMPLE_RATE,
+        "channels": 1,
+        "encoding": "pcm_s16le",
+        "language": language.fixed(),
+        "allowed_languages": language.allowed(),
+        "vocabulary": vocabulary,
+    });
+    write.send(Message::Text(start.to_string().into())).await.map_err(|e| e.to_string())?;
+
+    let mut transcript = String::new();
+    // Dropped as soon as one segment arrives without timing
+    let mut words = Some(Vec::new());
+    let mut sending = true;
+    loop {
+        tokio::select! {
+            chunk = audio_rx.recv(), if sending => match chunk {
+                Some(pcm) if pcm.is_empty() => {}
+                Some(pcm) => write.send(Message::Binary(pcm.into())).await.map_err(|e| e.to_string())?,
+                None => {
+                    write.send(Message::Text(r#"{"type":"stop"}"#.into())).await.map_err(|e| e.to_string())?;
+                    sending = false;
+                }
+            },
+            message = read.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => {
+                        if sending {
+                            return Err("Streaming server closed the connection".to_string());
+                        }
+                        return Ok(FullResponse { text: transcript, messages: Vec::new(), language: language.fixed(), words });
+                    }
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.to_string()),
+                };
+                match serde_json::from_str::<ServerEvent>(&text) {
+                    Ok(ServerEvent::Partial { text }) => {
+                        let mut shown = transcript.clone();
+                        append_segment(&mut shown, &text);
+                        on_partial(TranscriptPartial { text: shown, is_final: false });
+                    }
+                    Ok(ServerEvent::Final { text, words: segment_words }) => {
+                        append_segment(&mut transcript, &text);
+                        words = words.zip(segment_words).map(|(mut all, new)| {
+                            all.extend(new);
+                            all
+                        });
+                        on_partial(TranscriptPartial { text: transcript.clone(), is_final: true });
+                    }
+                    Ok(ServerEvent::Done { messages, language: detected }) => {
+                        let _ = write.close().await;
+                        return Ok(FullResponse { text: transcript, messages, language: detected.or_else(|| language.fixed()), words });
+                    }
+                    Ok(ServerEvent::Error { message }) => return Err(format!("Streaming error: {}", message)),
+                    Err(e) => println!("WARN: Ignoring unexpected streaming message: {}", e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tokio::net::TcpListener;
+
+    /// A stand-in streaming server for one connection. It answers the first audio
+    /// frame with `on_audio` and the stop message with `on_stop`; `close_early`
+    /// drops the connection after the first audio frame instead. Returns the URL
+    /// and a handle yielding the start message and the PCM byte count received.
+    async fn stand_in(on_audio: &'static [&'static str], on_stop: &'static [&'static str], close_early: bool) -> (String, JoinHandle<(serde_json::Value, usize)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let start = socket.next().await.unwrap().unwrap().into_text().unwrap();
+            let start: serde_json::Value = serde_json::from_str(&start).unwrap();
+            let mut received = 0;
+            let mut answered = false;
+            while let Some(Ok(message)) = socket.next().await {
+                match message {
+                    Message::Binary(pcm) => {
+                        received += pcm.len();
+                        if close_early {
+                            socket.close(None).await.unwrap();
+                            break;
+                        }
+                        if !answered {
+                            answered = true;
+                            for reply in on_audio {
+                                socket.send(Message::Text((*reply).into())).await.unwrap();
+                            }
+                        }
+                    }
+                    Message::Text(text) if text.contains("stop") => {
+                        for reply in on_stop {
+                            socket.send(Message::Text((*reply).into())).await.unwrap();
+                        }
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            (start, received)
+        });
+        (url, server)
+    }
+
+    fn german() -> LanguageConfig {
+        LanguageConfig { language: "de".into(), allowed: Vec::new() }
+    }
+
+    #[tokio::test]
+    async fn partials_and_finals_build_the_transcript() {
+        let (url, server) = stand_in(
+            &[
+                r#"{"type":"partial","text":"hel"}"#,
+                r#"{"type":"final","text":"hello","words":[{"word":"hello","start":0.1,"end":0.4,"confidence":0.9}]}"#,
+            ],
+            &[
+                r#"{"type":"partial","text":"wor"}"#,
+                r#"{"type":"final","text":"world","words":[{"word":"world","start":0.5,"end":0.8,"speaker":"A"}]}"#,
+                r#"{"type":"done","messages":[{"id":"m1","type_str":null,"title":"Hi","body":"News","action_url":null}],"language":"en"}"#,
+            ],
+            false,
+        ).await;
+
+        let (audio_tx, audio_rx) = unbounded_channel();
+        for _ in 0..3 {
+            audio_tx.send(vec![0u8; 640]).unwrap();
+        }
+        // Empty blocks are skipped, not sent as frames
+        audio_tx.send(Vec::new()).unwrap();
+        drop(audio_tx);
+
+        let vocabulary = vec![VocabularyTerm { term: "Voice2Text".into(), boost: Some(2.0) }];
+        let partials = RefCell::new(Vec::new());
+        let res = run_session(url, "token".into(), german(), vocabulary, audio_rx, |p| partials.borrow_mut().push((p.text, p.is_final)))
+            .await
+            .unwrap();
+
+        assert_eq!(res.text, "hello world");
+        assert_eq!(res.language.as_deref(), Some("en"));
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.messages[0].id, "m1");
+        let words = res.words.unwrap();
+        assert_eq!(words.len(), 2);
+        assert_eq!((words[0].word.as_str(), words[0].start, words[0].end, words[0].confidence), ("hello", 0.1, 0.4, Some(0.9)));
+        assert_eq!((words[1].word.as_str(), words[1].speaker.as_deref()), ("world", Some("A")));
+
+        assert_eq!(partials.into_inner(), vec![
+            ("hel".to_string(), false),
+            ("hello".to_string(), true),
+            ("hello wor".to_string(), false),
+            ("hello world".to_string(), true),
+        ]);
+
+        let (start, received) = server.await.unwrap();
+        assert_eq!(start["type"], "start");
+        assert_eq!(start["sample_rate"], TARGET_SAMPLE_RATE);
+        assert_eq!(start["language"], "de");
+        assert_eq!(start["vocabulary"][0]["term"], "Voice2Text");
+        assert_eq!(received, 3 * 640);
+    }
+
+    #[tokio::test]
+    async fn a_segment_without_timing_drops_the_words() {
+        let (url, _server) = stand_in(
+            &[],
+            &[
+                r#"{"type":"final","text":"hello","words":[{"word":"hello","start":0.1,"end":0.4}]}"#,
+                r#"{"type":"final","text":"world"}"#,
+                r#"{"type":"done"}"#,
+            ],
+            false,
+        ).await;
+        let (audio_tx, audio_rx) = unbounded_channel();
+        drop(audio_tx);
+
+        let res = run_session(url, "token".into(), german(), Vec::new(), audio_rx, |_| {}).await.unwrap();
+        assert_eq!(res.text, "hello world");
+        assert!(res.words.is_none());
+        // Without a detected language the fixed one is reported
+        assert_eq!(res.language.as_deref(), Some("de"));
+    }
+
+    #[tokio::test]
+    async fn server_error_fails_the_session() {
+        let (url, _server) = stand_in(
+            &[
+                r#"{"type":"partial","text":"hel"}"#,
+                r#"{"type":"error","message":"quota exceeded"}"#,
+            ],
+            &[],
+            false,
+        ).await;
+        let (audio_tx, audio_rx) = unbounded_channel();
+        audio_tx.send(vec![0u8; 640]).unwrap();
+
+        let partials = RefCell::new(Vec::new());
+        let err = run_session(url, "token".into(), german(), Vec::new(), audio_rx, |p| partials.borrow_mut().push(p.text))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err, "Streaming error: quota exceeded");
+        assert_eq!(partials.into_inner(), vec!["hel".to_string()]);
+        drop(audio_tx);
+    }
+
+    #[tokio::test]
+    async fn closing_before_the_recording_ends_fails_the_session() {
+        let (url, _server) = stand_in(&[], &[], true).await;
+        let (audio_tx, audio_rx) = unbounded_channel();
+        audio_tx.send(vec![0u8; 640]).unwrap();
+
+        let err = run_session(url, "token".into(), german(), Vec::new(), audio_rx, |_| {})
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err, "Streaming server closed the connection");
+        drop(audio_tx);
+    }
+}
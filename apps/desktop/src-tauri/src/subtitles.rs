@@ -0,0 +1,280 @@
+// Subtitles from word-level timing, written as SubRip (.srt) or WebVTT (.vtt).
+// Words are grouped into cues of at most two short lines; a cue also ends at a
+// pause, a sentence end, a change of speaker or once it has been shown long enough.
+
+use std::path::Path;
+use crate::error::AppError;
+use crate::transcribe::Word;
+
+// Common broadcast limits, readable at a glance
+const MAX_LINE_CHARS: usize = 42;
+const MAX_LINES: usize = 2;
+const MAX_CUE_SECS: f32 = 6.0;
+// A gap this long between words starts a new cue
+const PAUSE_SECS: f32 = 1.0;
+// Short cues stay up this long, unless the next one starts earlier
+const MIN_CUE_SECS: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// Chosen by the file extension, `.srt` or `.vtt`.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("srt") => Ok(Self::Srt),
+            Some("vtt") => Ok(Self::Vtt),
+            _ => Err(format!("Unsupported subtitle format: {} (use .srt or .vtt)", path.display())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start: f32,
+    pub end: f32,
+    pub lines: Vec<String>,
+    pub speaker: Option<String>,
+}
+
+impl Cue {
+    fn new(word: &Word, text: &str) -> Self {
+        Self { start: word.start, end: word.end, lines: vec![text.to_string()], speaker: word.speaker.clone() }
+    }
+
+    /// Adds the word if the cue has room for it; false means it starts the next cue.
+    fn try_add(&mut self, word: &Word, text: &str) -> bool {
+        let last = self.lines.last().map(String::as_str).unwrap_or("");
+        if word.start - self.end > PAUSE_SECS
+            || word.end - self.start > MAX_CUE_SECS
+            || word.speaker != self.speaker
+            || last.ends_with(['.', '?', '!'])
+        {
+            return false;
+        }
+        if last.chars().count() + 1 + text.chars().count() <= MAX_LINE_CHARS {
+            let line = self.lines.last_mut().unwrap();
+            line.push(' ');
+            line.push_str(text);
+        } else if self.lines.len() < MAX_LINES {
+            self.lines.push(text.to_string());
+        } else {
+            return false;
+        }
+        self.end = self.end.max(word.end);
+        true
+    }
+}
+
+/// Groups timed words into cues, in order.
+pub fn build_cues(words: &[Word]) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = Vec::new();
+    for word in words {
+        let text = word.word.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if !cues.last_mut().is_some_and(|cue| cue.try_add(word, text)) {
+            cues.push(Cue::new(word, text));
+        }
+    }
+
+    for i in 0..cues.len() {
+        let next_start = cues.get(i + 1).map_or(f32::INFINITY, |next| next.start);
+        let cue = &mut cues[i];
+        let held = (cue.start + MIN_CUE_SECS).min(next_start);
+        cue.end = cue.end.max(held);
+    }
+    cues
+}
+
+/// `hh:mm:ss,mmm` for SRT, `hh:mm:ss.mmm` for WebVTT.
+fn timestamp(secs: f32, separator: char) -> String {
+    let ms = (secs.max(0.0) as f64 * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02}{}{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, separator, ms % 1000)
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn render(cues: &[Cue], format: SubtitleFormat) -> String {
+    let mut out = String::new();
+    if format == SubtitleFormat::Vtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    for (i, cue) in cues.iter().enumerate() {
+        match format {
+            SubtitleFormat::Srt => {
+                out.push_str(&format!("{}\n{} --> {}\n", i + 1, timestamp(cue.start, ','), timestamp(cue.end, ',')));
+                out.push_str(&cue.lines.join("\n"));
+            }
+            SubtitleFormat::Vtt => {
+                out.push_str(&format!("{} --> {}\n", timestamp(cue.start, '.'), timestamp(cue.end, '.')));
+                if let Some(speaker) = &cue.speaker {
+                    out.push_str(&format!("<v {}>", escape_vtt(speaker)));
+                }
+                let lines: Vec<String> = cue.lines.iter().map(|l| escape_vtt(l)).collect();
+                out.push_str(&lines.join("\n"));
+            }
+        }
+        out.push_str("\n\n");
+    }
+    out
+}
+
+/// Writes subtitles for `words` to `path`, in the format its extension names.
+/// Returns the number of cues.
+pub fn write(path: &Path, words: &[Word]) -> Result<usize, AppError> {
+    let format = SubtitleFormat::from_path(path).map_err(AppError::Other)?;
+    let cues = build_cues(words);
+    if cues.is_empty() {
+        return Err(AppError::Other("No words to write as subtitles".to_string()));
+    }
+    std::fs::write(path, render(&cues, format))?;
+    Ok(cues.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, start: f32, end: f32) -> Word {
+        Word { word: text.to_string(), start, end, confidence: None, speaker: None }
+    }
+
+    /// `count` words of `text`, each `len` seconds long with `gap` between them.
+    fn run(text: &str, count: usize, len: f32, gap: f32) -> Vec<Word> {
+        (0..count).map(|i| {
+            let start = i as f32 * (len + gap);
+            word(text, start, start + len)
+        }).collect()
+    }
+
+    #[test]
+    fn lines_fill_up_to_42_characters_and_cues_hold_two() {
+        // Seven five letter words make 41 characters, an eighth would make 47
+        let cues = build_cues(&run("abcde", 16, 0.2, 0.05));
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, vec![["abcde"; 7].join(" "), ["abcde"; 7].join(" ")]);
+        assert_eq!(cues[1].lines, vec!["abcde abcde".to_string()]);
+        assert!(cues.iter().flat_map(|c| &c.lines).all(|l| l.chars().count() <= MAX_LINE_CHARS));
+        // Counted in characters, not bytes
+        let cues = build_cues(&run("äöüßé", 8, 0.2, 0.05));
+        assert_eq!(cues[0].lines.len(), 2);
+        assert_eq!(cues[0].lines[0].chars().count(), 41);
+    }
+
+    #[test]
+    fn cues_last_at_most_six_seconds() {
+        let cues = build_cues(&run("a", 30, 0.4, 0.1));
+        assert!(cues.len() > 1);
+        for cue in &cues {
+            assert!(cue.end - cue.start <= MAX_CUE_SECS, "{:?}", cue);
+        }
+        // Twelve words end at 5.9 s, the thirteenth at 6.4 s
+        assert_eq!(cues[0].lines[0].split(' ').count(), 12);
+    }
+
+    #[test]
+    fn a_pause_over_a_second_starts_a_new_cue() {
+        let cues = build_cues(&[word("one", 0.0, 0.5), word("two", 1.4, 1.8), word("three", 2.85, 3.2)]);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, vec!["one two"]);
+        assert_eq!(cues[1].lines, vec!["three"]);
+    }
+
+    #[test]
+    fn sentence_ends_and_speaker_changes_start_new_cues() {
+        let mut words = vec![word("Hi.", 0.0, 0.3), word("Who", 0.4, 0.6), word("is", 0.7, 0.8), word("there?", 0.9, 1.2)];
+        words[2].speaker = Some("B".to_string());
+        words[3].speaker = Some("B".to_string());
+        let cues = build_cues(&words);
+        let texts: Vec<String> = cues.iter().map(|c| c.lines.join(" ")).collect();
+        assert_eq!(texts, vec!["Hi.", "Who", "is there?"]);
+        assert_eq!(cues[2].speaker.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn short_cues_are_held_until_the_next_one() {
+        let cues = build_cues(&[word("Yes.", 0.0, 0.3), word("No.", 0.6, 0.8), word("Maybe.", 5.0, 5.2)]);
+        // Held for a second, but never over the start of the next cue
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 0.6));
+        assert_eq!((cues[1].start, cues[1].end), (0.6, 1.6));
+        assert_eq!((cues[2].start, cues[2].end), (5.0, 6.0));
+        // Longer cues keep their own end
+        let cues = build_cues(&[word("slowly", 0.0, 1.5)]);
+        assert_eq!(cues[0].end, 1.5);
+    }
+
+    #[test]
+    fn blank_words_are_skipped() {
+        let cues = build_cues(&[word(" ", 0.0, 0.1), word(" hello ", 0.2, 0.5), word("", 0.6, 0.7)]);
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].lines, vec!["hello"]);
+    }
+
+    #[test]
+    fn timestamps_round_to_milliseconds() {
+        assert_eq!(timestamp(0.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(-1.0, ','), "00:00:00,000");
+        assert_eq!(timestamp(61.5, ','), "00:01:01,500");
+        assert_eq!(timestamp(61.5, '.'), "00:01:01.500");
+        assert_eq!(timestamp(3599.999, ','), "00:59:59,999");
+        // Rounding carries into the hour
+        assert_eq!(timestamp(3599.9998, ','), "01:00:00,000");
+        assert_eq!(timestamp(3599.9998, '.'), "01:00:00.000");
+        assert_eq!(timestamp(7322.25, '.'), "02:02:02.250");
+        assert_eq!(timestamp(360_000.0, ','), "100:00:00,000");
+    }
+
+    #[test]
+    fn srt_and_vtt_layouts() {
+        let mut cues = build_cues(&[word("Hello", 0.0, 0.4), word("world.", 0.5, 1.25), word("Bye.", 3600.0, 3600.5)]);
+        cues[1].speaker = Some("A".to_string());
+        assert_eq!(
+            render(&cues, SubtitleFormat::Srt),
+            "1\n00:00:00,000 --> 00:00:01,250\nHello world.\n\n2\n01:00:00,000 --> 01:00:01,000\nBye.\n\n"
+        );
+        assert_eq!(
+            render(&cues, SubtitleFormat::Vtt),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.250\nHello world.\n\n01:00:00.000 --> 01:00:01.000\n<v A>Bye.\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_text_is_escaped() {
+        assert_eq!(escape_vtt("a < b & c"), "a &lt; b &amp; c");
+        // A cue line must not contain the timing arrow
+        assert_eq!(escape_vtt("then --> now"), "then --&gt; now");
+        assert_eq!(escape_vtt("&lt;"), "&amp;lt;");
+
+        let mut cues = build_cues(&[word("<b>bold</b>", 0.0, 1.0)]);
+        cues[0].speaker = Some("A&B".to_string());
+        assert!(render(&cues, SubtitleFormat::Vtt).contains("<v A&amp;B>&lt;b&gt;bold&lt;/b&gt;\n"));
+    }
+
+    #[test]
+    fn format_follows_the_extension() {
+        assert_eq!(SubtitleFormat::from_path(Path::new("talk.srt")), Ok(SubtitleFormat::Srt));
+        assert_eq!(SubtitleFormat::from_path(Path::new("C:\\out\\Talk.VTT")), Ok(SubtitleFormat::Vtt));
+        for path in ["talk.txt", "talk", "talk.srt.bak", ".srt", "talk.ass"] {
+            let err = SubtitleFormat::from_path(Path::new(path)).unwrap_err();
+            assert!(err.starts_with("Unsupported subtitle format"), "{}: {}", path, err);
+        }
+    }
+
+    #[test]
+    fn writing_needs_a_known_format_and_words() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(write(&dir.path().join("talk.txt"), &[word("hi", 0.0, 0.5)]).is_err());
+        assert!(write(&dir.path().join("talk.srt"), &[]).is_err());
+
+        let path = dir.path().join("talk.srt");
+        assert_eq!(write(&path, &[word("hi", 0.0, 0.5)]).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1\n00:00:00,000 --> 00:00:01,000\nhi\n\n");
+    }
+}
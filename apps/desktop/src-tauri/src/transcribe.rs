@@ -1,97 +1,876 @@
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
+use futures_util::stream::{self, StreamExt};
 use reqwest::multipart;
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::time::Duration;
+use crate::chunking::{append_merged, ChunkSplitter};
+use crate::error::{AppError, Server};
+use crate::local_whisper::LocalWhisperConfig;
+#[cfg(feature = "local-whisper")]
+use crate::local_whisper::LocalWhisperBackend;
+use crate::spool::WavPayload;
+use crate::vocabulary::{self, VocabularyTerm};
 
 #[derive(Deserialize, Debug)]
 pub struct TranscribeResponse {
     pub text: String,
     pub messages: Option<Vec<crate::auth::ServerMessage>>,
+    /// Language the server recognised, as an ISO 639-1 code.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Per-word timing, when the engine reports it.
+    #[serde(default)]
+    pub words: Option<Vec<Word>>,
+}
+
+/// One recognised word; times are seconds from the start of the recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Word {
+    pub word: String,
+    pub start: f32,
+    pub end: f32,
+    /// 0.0 to 1.0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// Speaker label from diarization, e.g. "A".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
 }
 
 pub struct FullResponse {
     pub text: String,
     pub messages: Vec<crate::auth::ServerMessage>,
+    pub language: Option<String>,
+    /// `None` unless every part of the recording came back with word timing.
+    pub words: Option<Vec<Word>>,
 }
 
-pub async fn send_to_api(app: &AppHandle, wav_data: Vec<u8>, jwt_token: &str) -> Result<FullResponse, String> {
-    const MAX_CHUNK_SIZE: usize = 3 * 1024 * 1024; // 3 MB safety limit
-    
-    if wav_data.len() <= MAX_CHUNK_SIZE {
-        return send_chunk(app, wav_data, 0, 1, jwt_token).await;
+/// Payload of the `transcription-progress` event, sent as each chunk of a split upload finishes.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChunkProgress {
+    /// 1-based index of the chunk that just finished.
+    pub chunk: usize,
+    pub completed: usize,
+    pub total: usize,
+}
+
+// Attempts per request, including the first
+const MAX_ATTEMPTS: u32 = 4;
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(8);
+// A server asking for a longer pause than this is treated as a hard failure
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(30);
+
+/// Why an upload failed, which decides whether it is worth sending again.
+#[derive(Debug)]
+enum RequestError {
+    /// Connection refused, reset or timed out before a response arrived.
+    Network(String),
+    /// The request could not be built (bad URL, header or body).
+    Invalid(String),
+    /// 429, with the server's `Retry-After` when it sent one.
+    RateLimited { retry_after: Option<Duration>, body: String },
+    /// 5xx, or 408 when the server gave up waiting for the upload.
+    Server { status: reqwest::StatusCode, body: String },
+    /// Any other 4xx; sending the same request again won't help.
+    Client { status: reqwest::StatusCode, body: String },
+}
+
+impl RequestError {
+    async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let retry_after = response.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            RequestError::RateLimited { retry_after, body }
+        } else if status.is_server_error() || status == reqwest::StatusCode::REQUEST_TIMEOUT {
+            RequestError::Server { status, body }
+        } else {
+            RequestError::Client { status, body }
+        }
     }
 
-    crate::write_to_log(app, &format!("API: Splitting large file ({} bytes)", wav_data.len()));
-    
-    let mut reader = hound::WavReader::new(std::io::Cursor::new(wav_data)).map_err(|e| e.to_string())?;
-    let spec = reader.spec();
-    let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
-    
-    let bytes_per_sample = (spec.bits_per_sample / 8) as usize;
-    let max_samples = MAX_CHUNK_SIZE / bytes_per_sample;
+    /// How long to wait before attempt `attempt + 1`, or `None` if retrying is pointless.
+    fn retry_delay(&self, attempt: u32) -> Option<Duration> {
+        match self {
+            RequestError::Network(_) | RequestError::Server { .. } => Some(backoff_delay(attempt)),
+            RequestError::RateLimited { retry_after: Some(wait), .. } => (*wait <= RETRY_AFTER_MAX).then_some(*wait),
+            RequestError::RateLimited { retry_after: None, .. } => Some(backoff_delay(attempt)),
+            RequestError::Invalid(_) | RequestError::Client { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Network(e) => write!(f, "Network error: {}", e),
+            RequestError::Invalid(e) => write!(f, "Invalid request: {}", e),
+            RequestError::RateLimited { body, .. } => write!(f, "API error ({}): {}", reqwest::StatusCode::TOO_MANY_REQUESTS, body),
+            RequestError::Server { status, body } | RequestError::Client { status, body } => write!(f, "API error ({}): {}", status, body),
+        }
+    }
+}
+
+impl RequestError {
+    /// Client errors mean different things depending on which server sent them.
+    fn into_app_error(self, server: Server) -> AppError {
+        let message = self.to_string();
+        match self {
+            RequestError::Network(_) => AppError::Network(message),
+            RequestError::Invalid(_) => AppError::Other(message),
+            RequestError::RateLimited { .. } => AppError::QuotaExceeded(message),
+            RequestError::Server { .. } => AppError::ServerError(message),
+            RequestError::Client { status, body } => AppError::from_status(server, status, &body),
+        }
+    }
+}
+
+/// `Retry-After` as either delay seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let wait = at.signed_duration_since(chrono::Utc::now()).to_std().unwrap_or(Duration::ZERO);
+    Some(wait)
+}
+
+/// Exponential backoff with equal jitter: half the delay is fixed, half random,
+/// so chunks that failed together don't all retry at the same instant.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exp = BACKOFF_BASE.saturating_mul(1 << attempt.saturating_sub(1).min(16)).min(BACKOFF_MAX);
+    let half = exp / 2;
+    let random = RandomState::new().build_hasher().finish();
+    half + Duration::from_millis(random % (half.as_millis() as u64 + 1))
+}
+
+/// Sends the request from `build` until it succeeds or fails for good. Every
+/// attempt carries the same `Idempotency-Key`, so a server that supports it
+/// processes (and bills) the chunk once even if a response got lost.
+/// Each retry is reported to `log`.
+async fn send_with_retry(label: &str, build: impl Fn() -> reqwest::RequestBuilder, log: impl Fn(&str)) -> Result<reqwest::Response, RequestError> {
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let mut attempt = 1;
+    loop {
+        let err = match build().header("Idempotency-Key", &idempotency_key).send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => RequestError::from_response(response).await,
+            Err(e) if e.is_builder() => RequestError::Invalid(e.to_string()),
+            Err(e) => RequestError::Network(e.to_string()),
+        };
+        let delay = match err.retry_delay(attempt) {
+            Some(delay) if attempt < MAX_ATTEMPTS => delay,
+            _ => return Err(err),
+        };
+        log(&format!("API: {} attempt {}/{} failed ({}), retrying in {} ms",
+            label, attempt, MAX_ATTEMPTS, err, delay.as_millis()));
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Which service turns audio into text.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// The Voice2Text proxy, authenticated with the client token.
+    #[default]
+    Proxy,
+    /// Any server implementing OpenAI's `/v1/audio/transcriptions`.
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// whisper.cpp on this machine, no network needed.
+    Local,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct OpenAiConfig {
+    /// Server root, e.g. `https://api.openai.com` or a team gateway.
+    pub base_url: String,
+    /// Lives in the OS credential store; never written out or sent to the UI.
+    #[serde(skip)]
+    pub api_key: String,
+    /// Whether a key is stored, which is all the UI gets to know.
+    pub api_key_set: bool,
+    pub model: String,
+}
+
+impl Default for OpenAiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.openai.com".to_string(),
+            api_key: String::new(),
+            api_key_set: false,
+            model: "whisper-1".to_string(),
+        }
+    }
+}
+
+/// Which language the dictation is in, as ISO 639-1 codes (`de`, `en`, ...).
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// Always transcribe as this language. Empty (or `auto`) detects it.
+    pub language: String,
+    /// When detecting, choose only among these. Empty allows every language.
+    pub allowed: Vec<String>,
+}
+
+impl LanguageConfig {
+    pub fn fixed(&self) -> Option<String> {
+        let code = self.language.trim().to_lowercase();
+        (!code.is_empty() && code != "auto").then_some(code)
+    }
+
+    pub fn allowed(&self) -> Vec<String> {
+        self.allowed.iter()
+            .map(|code| code.trim().to_lowercase())
+            .filter(|code| !code.is_empty())
+            .collect()
+    }
+}
+
+// Whisper's languages by the English name the OpenAI API reports them as
+const WHISPER_LANGUAGES: &[(&str, &str)] = &[
+    ("en", "english"), ("zh", "chinese"), ("de", "german"), ("es", "spanish"), ("ru", "russian"),
+    ("ko", "korean"), ("fr", "french"), ("ja", "japanese"), ("pt", "portuguese"), ("tr", "turkish"),
+    ("pl", "polish"), ("ca", "catalan"), ("nl", "dutch"), ("ar", "arabic"), ("sv", "swedish"),
+    ("it", "italian"), ("id", "indonesian"), ("hi", "hindi"), ("fi", "finnish"), ("vi", "vietnamese"),
+    ("he", "hebrew"), ("uk", "ukrainian"), ("el", "greek"), ("ms", "malay"), ("cs", "czech"),
+    ("ro", "romanian"), ("da", "danish"), ("hu", "hungarian"), ("ta", "tamil"), ("no", "norwegian"),
+    ("th", "thai"), ("ur", "urdu"), ("hr", "croatian"), ("bg", "bulgarian"), ("lt", "lithuanian"),
+    ("la", "latin"), ("mi", "maori"), ("ml", "malayalam"), ("cy", "welsh"), ("sk", "slovak"),
+    ("te", "telugu"), ("fa", "persian"), ("lv", "latvian"), ("bn", "bengali"), ("sr", "serbian"),
+    ("az", "azerbaijani"), ("sl", "slovenian"), ("kn", "kannada"), ("et", "estonian"), ("mk", "macedonian"),
+    ("br", "breton"), ("eu", "basque"), ("is", "icelandic"), ("hy", "armenian"), ("ne", "nepali"),
+    ("mn", "mongolian"), ("bs", "bosnian"), ("kk", "kazakh"), ("sq", "albanian"), ("sw", "swahili"),
+    ("gl", "galician"), ("mr", "marathi"), ("pa", "punjabi"), ("si", "sinhala"), ("km", "khmer"),
+    ("sn", "shona"), ("yo", "yoruba"), ("so", "somali"), ("af", "afrikaans"), ("oc", "occitan"),
+    ("ka", "georgian"), ("be", "belarusian"), ("tg", "tajik"), ("sd", "sindhi"), ("gu", "gujarati"),
+    ("am", "amharic"), ("yi", "yiddish"), ("lo", "lao"), ("uz", "uzbek"), ("fo", "faroese"),
+    ("ht", "haitian creole"), ("ps", "pashto"), ("tk", "turkmen"), ("nn", "nynorsk"), ("mt", "maltese"),
+    ("sa", "sanskrit"), ("lb", "luxembourgish"), ("my", "myanmar"), ("bo", "tibetan"), ("tl", "tagalog"),
+    ("mg", "malagasy"), ("as", "assamese"), ("tt", "tatar"), ("haw", "hawaiian"), ("ln", "lingala"),
+    ("ha", "hausa"), ("ba", "bashkir"), ("jw", "javanese"), ("su", "sundanese"), ("yue", "cantonese"),
+];
+
+/// ISO code for a language reported by a transcription service, which may
+/// give either the code itself or Whisper's English name (`german`).
+fn language_code(reported: &str) -> Option<String> {
+    let reported = reported.trim().to_lowercase();
+    WHISPER_LANGUAGES.iter()
+        .find(|(code, name)| *code == reported || *name == reported)
+        .map(|(code, _)| code.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TranscriptionConfig {
+    pub backend: BackendKind,
+    pub openai: OpenAiConfig,
+    pub local: LocalWhisperConfig,
+    /// Send audio while recording and show partial results.
+    pub streaming: crate::streaming::StreamingConfig,
+    pub language: LanguageConfig,
+    /// Names and jargon to favour, sent with every request.
+    pub vocabulary: Vec<VocabularyTerm>,
+}
+
+pub type BackendFuture<'a> = Pin<Box<dyn Future<Output = Result<FullResponse, AppError>> + Send + 'a>>;
+
+/// A service that transcribes one WAV upload at a time.
+pub trait TranscriptionBackend: Send + Sync {
+    /// Short name recorded on history entries.
+    fn name(&self) -> &'static str;
+    /// Largest WAV accepted in a single request; longer audio is split.
+    fn max_upload_bytes(&self) -> usize;
+    /// Chunks of a split recording sent at the same time.
+    fn max_concurrency(&self) -> usize {
+        1
+    }
+    /// Transcribes chunk `chunk_idx` of `total` (index 0 of 1 for unsplit audio).
+    fn transcribe(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> BackendFuture<'_>;
+}
+
+/// Builds the backend selected in the settings.
+pub fn backend_from_config(app: &AppHandle, config: &TranscriptionConfig, jwt_token: &str) -> Box<dyn TranscriptionBackend> {
+    match config.backend {
+        BackendKind::Proxy => Box::new(ProxyBackend {
+            app: app.clone(),
+            jwt_token: tokio::sync::Mutex::new(jwt_token.to_string()),
+            language: config.language.clone(),
+            vocabulary: config.vocabulary.clone(),
+        }),
+        BackendKind::OpenAi => Box::new(OpenAiBackend {
+            app: app.clone(),
+            config: config.openai.clone(),
+            language: config.language.clone(),
+            prompt: vocabulary::prompt(&config.vocabulary),
+        }),
+        #[cfg(feature = "local-whisper")]
+        BackendKind::Local => Box::new(local_backend(app, config)),
+        #[cfg(not(feature = "local-whisper"))]
+        BackendKind::Local => Box::new(crate::local_whisper::UnavailableBackend),
+    }
+}
+
+#[cfg(feature = "local-whisper")]
+fn local_backend(app: &AppHandle, config: &TranscriptionConfig) -> LocalWhisperBackend {
+    LocalWhisperBackend::new(app.clone(), config.local.clone(), config.language.clone(), vocabulary::prompt(&config.vocabulary))
+}
+
+/// Transcribes with `backend`; if that fails and a local model is set up for
+/// fallback, transcribes offline instead. Returns the response together with
+/// the name of the backend that produced it.
+pub async fn transcribe_with_fallback(
+    app: &AppHandle,
+    config: &TranscriptionConfig,
+    backend: &dyn TranscriptionBackend,
+    wav: &WavPayload,
+) -> Result<(FullResponse, &'static str), AppError> {
+    match send_to_api(app, backend, wav).await {
+        Ok(res) => Ok((res, backend.name())),
+        Err(err) => local_fallback(app, config, backend.name(), wav, err).await,
+    }
+}
+
+/// Retries offline after `err` when a local model is set up for fallback.
+#[cfg(feature = "local-whisper")]
+async fn local_fallback(
+    app: &AppHandle,
+    config: &TranscriptionConfig,
+    failed: &str,
+    wav: &WavPayload,
+    err: AppError,
+) -> Result<(FullResponse, &'static str), AppError> {
+    if config.backend == BackendKind::Local || !config.local.fallback || !config.local.is_configured() {
+        return Err(err);
+    }
+
+    crate::write_to_log(app, &format!("API: {} failed ({}), falling back to local Whisper", failed, err));
+    let local = local_backend(app, config);
+    match send_to_api(app, &local, wav).await {
+        Ok(res) => Ok((res, local.name())),
+        Err(local_err) => Err(err.map_message(|m| format!("{} (local fallback failed: {})", m, local_err))),
+    }
+}
+
+#[cfg(not(feature = "local-whisper"))]
+async fn local_fallback(
+    _app: &AppHandle,
+    _config: &TranscriptionConfig,
+    _failed: &str,
+    _wav: &WavPayload,
+    err: AppError,
+) -> Result<(FullResponse, &'static str), AppError> {
+    Err(err)
+}
+
+pub async fn send_to_api(app: &AppHandle, backend: &dyn TranscriptionBackend, wav: &WavPayload) -> Result<FullResponse, AppError> {
+    let log = |msg: &str| crate::write_to_log(app, msg);
+    let progress = |progress: ChunkProgress| {
+        let _ = app.emit("transcription-progress", progress);
+    };
+    upload(backend, wav, log, progress).await
+}
+
+/// Transcribes `wav` in one request, or split into chunks when it is larger
+/// than the backend takes, reporting each chunk as it finishes.
+pub(crate) async fn upload(
+    backend: &dyn TranscriptionBackend,
+    wav: &WavPayload,
+    log: impl Fn(&str),
+    mut on_progress: impl FnMut(ChunkProgress),
+) -> Result<FullResponse, AppError> {
+    let max_chunk_size = backend.max_upload_bytes();
     
-    let chunks: Vec<&[i16]> = samples.chunks(max_samples).collect();
+    if wav.len() <= max_chunk_size {
+        return backend.transcribe(wav.to_bytes().map_err(AppError::Io)?, 0, 1).await;
+    }
+
+    log(&format!("API: Splitting large file ({} bytes)", wav.len()));
+
+    let mut chunks = ChunkSplitter::new(wav, max_chunk_size).map_err(AppError::Io)?;
     let total_chunks = chunks.len();
-    
+    let spans = chunks.spans();
+    let concurrency = backend.max_concurrency().clamp(1, total_chunks.max(1));
+    log(&format!("API: Uploading {} chunks, {} at a time", total_chunks, concurrency));
+
+    // Chunks are encoded only when a slot frees up, so at most `concurrency` are held in memory
+    let mut next_idx = 0;
+    let uploads = std::iter::from_fn(move || {
+        let chunk = chunks.next_chunk().transpose()?;
+        next_idx += 1;
+        let chunk_idx = next_idx;
+        Some(async move {
+            let result = match chunk {
+                Ok(chunk_wav) => backend.transcribe(chunk_wav, chunk_idx, total_chunks).await,
+                Err(e) => Err(AppError::Io(e)),
+            };
+            (chunk_idx, result)
+        })
+    });
+    let mut pending = stream::iter(uploads).buffer_unordered(concurrency);
+
+    let mut results: Vec<Option<FullResponse>> = (0..total_chunks).map(|_| None).collect();
+    let mut completed = 0;
+    while let Some((chunk_idx, result)) = pending.next().await {
+        // Returning drops `pending`, which cancels the uploads still in flight
+        let res = result.map_err(|e| e.map_message(|m| format!("Chunk {}/{} failed: {}", chunk_idx, total_chunks, m)))?;
+        completed += 1;
+        on_progress(ChunkProgress { chunk: chunk_idx, completed, total: total_chunks });
+        results[chunk_idx - 1] = Some(res);
+    }
+
+    // Reassemble in recording order, whatever order the chunks finished in
     let mut full_transcript = String::new();
     let mut all_messages = Vec::new();
+    let mut language = None;
+    let mut words = Some(Vec::new());
+    for (i, res) in results.into_iter().flatten().enumerate() {
+        append_merged(&mut full_transcript, &res.text);
+        all_messages.extend(res.messages);
+        // The opening chunk has the most context for detection
+        language = language.or(res.language);
+        // Each overlap is split at the cut: the earlier chunk keeps what came before it
+        let from = if i == 0 { f32::NEG_INFINITY } else { spans[i - 1].1 };
+        let to = spans.get(i + 1).map_or(f32::INFINITY, |_| spans[i].1);
+        words = words.zip(res.words).map(|(mut all, chunk_words)| {
+            all.extend(place_words(chunk_words, spans[i].0, from, to));
+            all
+        });
+    }
 
-    for (i, chunk) in chunks.iter().enumerate() {
-        let mut chunk_wav = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut chunk_wav);
-        let mut writer = hound::WavWriter::new(&mut cursor, spec).map_err(|e| e.to_string())?;
-        
-        for &s in *chunk {
-            writer.write_sample(s).map_err(|e| e.to_string())?;
-        }
-        writer.finalize().map_err(|e| e.to_string())?;
-        
-        let res = send_chunk(app, chunk_wav, i + 1, total_chunks, jwt_token).await?;
-        if !full_transcript.is_empty() {
-            full_transcript.push(' ');
+    Ok(FullResponse { text: full_transcript, messages: all_messages, language, words })
+}
+
+/// Moves a chunk's word times (relative to the chunk starting at `offset`) onto the
+/// recording's timeline, keeping only words centred in `[from, to)`.
+fn place_words(words: Vec<Word>, offset: f32, from: f32, to: f32) -> impl Iterator<Item = Word> {
+    words.into_iter()
+        .map(move |w| Word { start: w.start + offset, end: w.end + offset, ..w })
+        .filter(move |w| {
+            let middle = (w.start + w.end) / 2.0;
+            middle >= from && middle < to
+        })
+}
+
+/// The Voice2Text proxy at `/api/transcribe`.
+pub struct ProxyBackend {
+    app: AppHandle,
+    // Replaced when the service rejects it; held while refreshing so chunks
+    // that fail together wait for a single refresh
+    jwt_token: tokio::sync::Mutex<String>,
+    language: LanguageConfig,
+    vocabulary: Vec<VocabularyTerm>,
+}
+
+impl TranscriptionBackend for ProxyBackend {
+    fn name(&self) -> &'static str {
+        "proxy"
+    }
+
+    fn max_upload_bytes(&self) -> usize {
+        3 * 1024 * 1024 // 3 MB safety limit
+    }
+
+    fn max_concurrency(&self) -> usize {
+        3
+    }
+
+    fn transcribe(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> BackendFuture<'_> {
+        Box::pin(self.send_chunk(wav_data, chunk_idx, total))
+    }
+}
+
+/// OpenAI-compatible `/v1/audio/transcriptions`, e.g. OpenAI itself or a self-hosted gateway.
+pub struct OpenAiBackend {
+    app: AppHandle,
+    config: OpenAiConfig,
+    language: LanguageConfig,
+    prompt: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    text: String,
+    /// Detected language, only part of the `verbose_json` format.
+    #[serde(default)]
+    language: Option<String>,
+    /// Word timing, returned when asked for with `timestamp_granularities[]=word`.
+    #[serde(default)]
+    words: Option<Vec<Word>>,
+}
+
+impl OpenAiBackend {
+    fn endpoint(&self) -> String {
+        let base = self.config.base_url.trim_end_matches('/');
+        // Accept the root with or without the version segment
+        let base = base.strip_suffix("/v1").unwrap_or(base);
+        format!("{}/v1/audio/transcriptions", base)
+    }
+
+    async fn send(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> Result<FullResponse, AppError> {
+        let app = &self.app;
+        crate::write_to_log(app, &format!("API: Sending chunk {}/{} ({} bytes)", chunk_idx, total, wav_data.len()));
+
+        let client = crate::api::client();
+        let api_url = self.endpoint();
+        crate::write_to_log(app, &format!("API URL: {}", api_url));
+
+        let build = || {
+            let part = multipart::Part::bytes(wav_data.clone())
+                .file_name("audio.wav")
+                .mime_str("audio/wav").unwrap();
+            let mut form = multipart::Form::new()
+                .part("file", part)
+                .text("model", self.config.model.clone())
+                .text("response_format", "verbose_json")
+                .text("timestamp_granularities[]", "word");
+            // The API takes a single language; an allowed list falls back to detection
+            if let Some(language) = self.language.fixed() {
+                form = form.text("language", language);
+            }
+            if let Some(prompt) = &self.prompt {
+                form = form.text("prompt", prompt.clone());
+            }
+            let request = client.post(&api_url).timeout(crate::api::TRANSCRIBE_TIMEOUT).multipart(form);
+            if self.config.api_key.is_empty() {
+                request
+            } else {
+                request.bearer_auth(&self.config.api_key)
+            }
+        };
+        let label = format!("chunk {}/{}", chunk_idx, total);
+        let response = send_with_retry(&label, build, |msg| crate::write_to_log(app, msg)).await.map_err(|e| e.into_app_error(Server::ThirdParty))?;
+
+        let res: OpenAiResponse = response.json().await?;
+        let language = res.language.as_deref().and_then(language_code).or_else(|| self.language.fixed());
+        let words = res.words.map(|words| words.into_iter().map(|w| Word { word: w.word.trim().to_string(), ..w }).collect());
+        Ok(FullResponse { text: res.text, messages: Vec::new(), language, words })
+    }
+}
+
+impl TranscriptionBackend for OpenAiBackend {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn max_upload_bytes(&self) -> usize {
+        24 * 1024 * 1024 // OpenAI rejects files over 25 MB
+    }
+
+    fn max_concurrency(&self) -> usize {
+        3
+    }
+
+    fn transcribe(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> BackendFuture<'_> {
+        Box::pin(self.send(wav_data, chunk_idx, total))
+    }
+}
+
+impl ProxyBackend {
+    /// A token to use instead of the rejected `stale` one.
+    async fn refresh_token(&self, stale: &str) -> Result<String, AppError> {
+        let mut token = self.jwt_token.lock().await;
+        if *token != stale {
+            // Another chunk has refreshed it already
+            return Ok(token.clone());
         }
-        full_transcript.push_str(&res.text);
-        all_messages.extend(res.messages);
+        let status = crate::refresh_status(&self.app).await?;
+        *token = status.token.clone();
+        Ok(status.token)
     }
 
-    Ok(FullResponse { text: full_transcript, messages: all_messages })
+    async fn send_chunk(&self, wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> Result<FullResponse, AppError> {
+        let app = &self.app;
+        crate::write_to_log(app, &format!("API: Sending chunk {}/{} ({} bytes)", chunk_idx, total, wav_data.len()));
+
+        let client = crate::api::client();
+        let api_url = crate::api::url("/api/transcribe");
+        println!("INFO: Sending chunk to API: {}", api_url);
+        crate::write_to_log(app, &format!("API URL: {}", api_url));
+
+        let vocabulary = (!self.vocabulary.is_empty())
+            .then(|| serde_json::to_string(&self.vocabulary))
+            .transpose()
+            .map_err(|e| AppError::Other(e.to_string()))?;
+
+        let label = format!("chunk {}/{}", chunk_idx, total);
+        let mut token = self.jwt_token.lock().await.clone();
+        let mut refreshed = false;
+        let response = loop {
+            // The multipart body is consumed by each send, so every attempt builds its own
+            let build = || {
+                let part = multipart::Part::bytes(wav_data.clone())
+                    .file_name("audio.wav")
+                    .mime_str("audio/wav").unwrap();
+                let mut form = multipart::Form::new().part("audio", part);
+                // `language` pins the language; `allowed_languages` (comma separated) limits detection
+                if let Some(code) = self.language.fixed() {
+                    form = form.text("language", code);
+                } else if !self.language.allowed().is_empty() {
+                    form = form.text("allowed_languages", self.language.allowed().join(","));
+                }
+                // `vocabulary` is a JSON array of {"term", "boost"}
+                if let Some(vocabulary) = &vocabulary {
+                    form = form.text("vocabulary", vocabulary.clone());
+                }
+                client.post(&api_url)
+                    .timeout(crate::api::TRANSCRIBE_TIMEOUT)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .multipart(form)
+            };
+            match send_with_retry(&label, build, |msg| crate::write_to_log(app, msg)).await {
+                Ok(response) => break response,
+                // The token expired while the app sat idle: get a new one and send the chunk again, once
+                Err(RequestError::Client { status, .. }) if status == reqwest::StatusCode::UNAUTHORIZED && !refreshed => {
+                    crate::write_to_log(app, &format!("API: {} was rejected (401), refreshing the token", label));
+                    token = self.refresh_token(&token).await?;
+                    refreshed = true;
+                }
+                Err(e) => return Err(e.into_app_error(Server::Voice2Text)),
+            }
+        };
+
+        let res: TranscribeResponse = response.json().await?;
+        Ok(FullResponse {
+            text: res.text,
+            messages: res.messages.unwrap_or_default(),
+            language: res.language.or_else(|| self.language.fixed()),
+            words: res.words,
+        })
+    }
 }
 
-async fn send_chunk(app: &AppHandle, wav_data: Vec<u8>, chunk_idx: usize, total: usize, jwt_token: &str) -> Result<FullResponse, String> {
-    crate::write_to_log(app, &format!("API: Sending chunk {}/{} ({} bytes)", chunk_idx, total, wav_data.len()));
-    
-    let client = reqwest::Client::new();
-    let part = multipart::Part::bytes(wav_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav").unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Returns one word at each end and one in the middle of every chunk, with
+    /// chunk relative times; later chunks answer sooner, so they finish in reverse.
+    struct ReverseBackend {
+        spans: Vec<(f32, f32)>,
+    }
 
-    let form = multipart::Form::new().part("audio", part);
+    impl TranscriptionBackend for ReverseBackend {
+        fn name(&self) -> &'static str {
+            "reverse"
+        }
 
-    let api_base = if cfg!(debug_assertions) {
-        "http://localhost:3000"
-    } else {
-        "https://voice2text.runitfast.xyz"
-    };
+        fn max_upload_bytes(&self) -> usize {
+            44 + 2 * 3 * 16000
+        }
 
-    let api_url = format!("{}/api/transcribe", api_base);
-    println!("INFO: Sending chunk to API: {}", api_url);
-    crate::write_to_log(app, &format!("API URL: {}", api_url));
+        fn max_concurrency(&self) -> usize {
+            8
+        }
 
-    let response = client.post(&api_url)
-        .header("Authorization", format!("Bearer {}", jwt_token))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        fn transcribe(&self, _wav_data: Vec<u8>, chunk_idx: usize, total: usize) -> BackendFuture<'_> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(30 * (total - chunk_idx) as u64)).await;
+                let (start, end) = self.spans[chunk_idx - 1];
+                let len = end - start;
+                let word = |name: &str, start: f32| Word { word: format!("{}{}", name, chunk_idx), start, end: start + 0.2, confidence: None, speaker: None };
+                Ok(FullResponse {
+                    text: format!("part{}", chunk_idx),
+                    messages: Vec::new(),
+                    language: None,
+                    words: Some(vec![word("head", 0.1), word("body", len / 2.0), word("tail", len - 0.3)]),
+                })
+            })
+        }
+    }
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let err_body = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("API error ({}): {}", status, err_body));
+    fn silence(secs: usize) -> WavPayload {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 16000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut bytes = Vec::new();
+        let mut writer = hound::WavWriter::new(Cursor::new(&mut bytes), spec).unwrap();
+        for _ in 0..secs * 16000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        WavPayload::Memory(bytes)
+    }
+
+    #[tokio::test]
+    async fn chunks_finishing_in_reverse_are_reassembled_in_order() {
+        let wav = silence(8);
+        let mut backend = ReverseBackend { spans: Vec::new() };
+        backend.spans = ChunkSplitter::new(&wav, backend.max_upload_bytes()).unwrap().spans();
+        let spans = backend.spans.clone();
+        let total = spans.len();
+        assert!(total >= 3, "{:?}", spans);
+
+        let mut finished = Vec::new();
+        let res = upload(&backend, &wav, |_| {}, |p| finished.push(p.chunk)).await.unwrap();
+        assert_eq!(finished, (1..=total).rev().collect::<Vec<_>>());
+        let parts: Vec<String> = (1..=total).map(|i| format!("part{}", i)).collect();
+        assert_eq!(res.text, parts.join(" "));
+
+        // Every chunk after the first starts in the overlap, where the earlier chunk's words win
+        let mut expected = vec![("head1".to_string(), 0.1)];
+        for (i, &(start, end)) in spans.iter().enumerate() {
+            expected.push((format!("body{}", i + 1), start + (end - start) / 2.0));
+            expected.push((format!("tail{}", i + 1), end - 0.3));
+        }
+        let words = res.words.unwrap();
+        assert_eq!(words.iter().map(|w| w.word.clone()).collect::<Vec<_>>(), expected.iter().map(|e| e.0.clone()).collect::<Vec<_>>());
+        for (word, (_, start)) in words.iter().zip(&expected) {
+            assert!((word.start - start).abs() < 1e-3 && (word.end - start - 0.2).abs() < 1e-3, "{:?} should start at {}", word, start);
+        }
+    }
+
+    #[test]
+    fn words_are_offset_and_kept_only_between_the_cuts() {
+        let word = |start: f32| Word { word: format!("w{}", start), start, end: start + 0.2, confidence: None, speaker: None };
+        let words = vec![word(0.0), word(0.5), word(0.8), word(1.9)];
+        let placed: Vec<f32> = place_words(words, 10.0, 10.6, 11.5).map(|w| w.start).collect();
+        // Centred at 10.1 and 12.0 they fall outside; the range includes its start at 10.6
+        assert_eq!(placed, vec![10.5, 10.8]);
+    }
+
+    #[test]
+    fn api_key_is_neither_saved_nor_loaded() {
+        let config = OpenAiConfig { api_key: "sk-secret".to_string(), api_key_set: true, ..OpenAiConfig::default() };
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("sk-secret") && !json.contains("\"api_key\""), "{}", json);
+
+        let loaded: OpenAiConfig = serde_json::from_str(r#"{"api_key": "sk-plain", "api_key_set": true}"#).unwrap();
+        assert!(loaded.api_key.is_empty());
+    }
+
+    #[test]
+    fn retries_only_failures_that_may_pass() {
+        let status = reqwest::StatusCode::BAD_REQUEST;
+        let body = String::new();
+        assert!(RequestError::Network("reset".into()).retry_delay(1).is_some());
+        assert!(RequestError::Server { status: reqwest::StatusCode::BAD_GATEWAY, body: body.clone() }.retry_delay(1).is_some());
+        assert!(RequestError::RateLimited { retry_after: None, body: body.clone() }.retry_delay(1).is_some());
+        assert_eq!(RequestError::RateLimited { retry_after: Some(Duration::from_secs(3)), body: body.clone() }.retry_delay(1), Some(Duration::from_secs(3)));
+        assert_eq!(RequestError::RateLimited { retry_after: Some(RETRY_AFTER_MAX), body: body.clone() }.retry_delay(1), Some(RETRY_AFTER_MAX));
+        assert_eq!(RequestError::RateLimited { retry_after: Some(RETRY_AFTER_MAX + Duration::from_secs(1)), body: body.clone() }.retry_delay(1), None);
+        assert_eq!(RequestError::Invalid("bad header".into()).retry_delay(1), None);
+        assert_eq!(RequestError::Client { status, body }.retry_delay(1), None);
     }
 
-    let res: TranscribeResponse = response.json().await.map_err(|e| e.to_string())?;
-    Ok(FullResponse {
-        text: res.text,
-        messages: res.messages.unwrap_or_default()
-    })
+    #[test]
+    fn parses_retry_after_seconds_and_dates() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+
+        let later = (chrono::Utc::now() + chrono::Duration::seconds(20)).to_rfc2822();
+        let wait = parse_retry_after(&later).unwrap();
+        assert!(wait > Duration::from_secs(18) && wait <= Duration::from_secs(20), "{:?}", wait);
+        // A date already past means no wait at all
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_up_to_the_cap() {
+        for (attempt, ms) in [(1, 500), (2, 1000), (3, 2000), (4, 4000), (5, 8000), (6, 8000), (40, 8000)] {
+            let exp = Duration::from_millis(ms);
+            for _ in 0..20 {
+                let delay = backoff_delay(attempt);
+                assert!(delay >= exp / 2 && delay <= exp, "attempt {}: {:?} outside {:?}", attempt, delay, exp);
+            }
+        }
+    }
+
+    /// A stand-in HTTP server answering one connection per reply in `replies`.
+    /// Yields the head of every request it received.
+    async fn stand_in(replies: Vec<&'static str>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut heads = Vec::new();
+            for reply in replies {
+                let (mut tcp, _) = listener.accept().await.unwrap();
+                let mut head = Vec::new();
+                while !head.ends_with(b"\r\n\r\n") {
+                    head.push(tcp.read_u8().await.unwrap());
+                }
+                let head = String::from_utf8(head).unwrap().to_lowercase();
+                let length = head.lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .map_or(0, |v| v.trim().parse().unwrap());
+                let mut body = vec![0; length];
+                tcp.read_exact(&mut body).await.unwrap();
+                tcp.write_all(reply.as_bytes()).await.unwrap();
+                heads.push(head);
+            }
+            heads
+        });
+        (url, server)
+    }
+
+    async fn send_to(url: &str) -> Result<reqwest::Response, RequestError> {
+        let client = reqwest::Client::new();
+        send_with_retry("test", || client.post(url).body("audio"), |_| {}).await
+    }
+
+    fn idempotency_key(head: &str) -> &str {
+        head.lines().find_map(|l| l.strip_prefix("idempotency-key:")).unwrap().trim()
+    }
+
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+    const BUSY: &str = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 0\r\nContent-Length: 4\r\nConnection: close\r\n\r\nbusy";
+
+    #[tokio::test]
+    async fn retries_keep_the_idempotency_key() {
+        let (url, server) = stand_in(vec![
+            BUSY,
+            "HTTP/1.1 408 Request Timeout\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            OK,
+        ]).await;
+        assert!(send_to(&url).await.is_ok());
+        let heads = server.await.unwrap();
+        assert_eq!(heads.len(), 4);
+        let key = idempotency_key(&heads[0]);
+        assert!(!key.is_empty());
+        assert!(heads.iter().all(|h| idempotency_key(h) == key), "{:?}", heads);
+
+        // A new request gets a key of its own
+        let (url, server) = stand_in(vec![OK]).await;
+        send_to(&url).await.unwrap();
+        assert_ne!(idempotency_key(&server.await.unwrap()[0]), key);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        for reply in [
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 3\r\nConnection: close\r\n\r\nbad",
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 413 Payload Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ] {
+            let (url, server) = stand_in(vec![reply]).await;
+            let err = send_to(&url).await.unwrap_err();
+            assert!(matches!(err, RequestError::Client { .. }), "{}", err);
+            assert_eq!(server.await.unwrap().len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_last_attempt() {
+        let (url, server) = stand_in(vec![BUSY; MAX_ATTEMPTS as usize]).await;
+        let err = send_to(&url).await.unwrap_err();
+        assert!(matches!(err, RequestError::RateLimited { .. }), "{}", err);
+        assert_eq!(server.await.unwrap().len(), MAX_ATTEMPTS as usize);
+    }
 }
@@ -0,0 +1,365 @@
+// Voice Activity Detection
+// Energy-based speech detection used to trim silence before upload.
+
+use serde::{Serialize, Deserialize};
+use crate::spool::{WavPayload, WavSink};
+use crate::transcribe::Word;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VadConfig {
+    pub enabled: bool,
+    /// Analysis frame length.
+    pub frame_ms: u32,
+    /// How far above the estimated noise floor a frame must be to count as speech.
+    pub threshold_db: f32,
+    /// Frames quieter than this are never speech, whatever the noise floor.
+    pub min_level_db: f32,
+    /// Speech bursts shorter than this are treated as clicks or key noise.
+    pub min_speech_ms: u32,
+    /// Audio kept before and after each speech region so onsets aren't clipped.
+    pub padding_ms: u32,
+    /// Internal pauses longer than this are shortened to this length. 0 keeps pauses intact.
+    pub max_pause_ms: u32,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            frame_ms: 20,
+            threshold_db: 12.0,
+            min_level_db: -55.0,
+            min_speech_ms: 60,
+            padding_ms: 200,
+            max_pause_ms: 1000,
+        }
+    }
+}
+
+pub struct VadResult {
+    pub wav: WavPayload,
+    pub original_sec: f32,
+    pub trimmed_sec: f32,
+    pub time_map: TimeMap,
+}
+
+/// Where the trimmed audio came from in the recording, so times reported for
+/// the trimmed audio can be moved back. The default maps every time to itself.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TimeMap {
+    /// Kept sample ranges of the recording, in order.
+    regions: Vec<(usize, usize)>,
+    sample_rate: u32,
+}
+
+impl TimeMap {
+    /// The time in the recording that `trimmed_sec` into the trimmed audio was cut from.
+    pub fn to_original(&self, trimmed_sec: f32) -> f32 {
+        let rate = self.sample_rate.max(1) as f64;
+        let mut rest = trimmed_sec.max(0.0) as f64;
+        for &(start, end) in &self.regions {
+            let len = (end - start) as f64 / rate;
+            if rest < len {
+                return (start as f64 / rate + rest) as f32;
+            }
+            rest -= len;
+        }
+        // Past the end of the trimmed audio, e.g. by rounding
+        self.regions.last().map_or(trimmed_sec, |&(_, end)| (end as f64 / rate + rest) as f32)
+    }
+
+    /// Moves word timings of the trimmed audio onto the recording's timeline.
+    pub fn restore_words(&self, words: &mut [Word]) {
+        for word in words {
+            word.start = self.to_original(word.start);
+            word.end = self.to_original(word.end);
+        }
+    }
+}
+
+/// Trims leading/trailing non-speech from a 16-bit mono WAV and collapses long pauses.
+/// Reads the input twice (levels, then copy) so long recordings never sit in memory.
+/// Returns `None` when no speech is detected and the input should be used unchanged.
+pub fn trim_silence(wav: &WavPayload, config: &VadConfig) -> Result<Option<VadResult>, String> {
+    let mut reader = wav.reader()?;
+    let spec = reader.spec();
+    let frame_len = (spec.sample_rate as usize * config.frame_ms.max(1) as usize / 1000).max(1);
+
+    let mut levels = Vec::new();
+    let mut sum_sq = 0.0f64;
+    let mut in_frame = 0usize;
+    let mut total = 0usize;
+    for sample in reader.samples::<i16>() {
+        let v = sample.map_err(|e| e.to_string())? as f64 / 32768.0;
+        sum_sq += v * v;
+        in_frame += 1;
+        total += 1;
+        if in_frame == frame_len {
+            levels.push(level_db(sum_sq, in_frame));
+            sum_sq = 0.0;
+            in_frame = 0;
+        }
+    }
+    if in_frame > 0 {
+        levels.push(level_db(sum_sq, in_frame));
+    }
+
+    let keep = speech_regions(&levels, frame_len, total, spec.sample_rate, config);
+    if keep.is_empty() {
+        return Ok(None);
+    }
+
+    let mut sink = WavSink::new(spec, !matches!(wav, WavPayload::Memory(_)))?;
+    let mut kept = 0usize;
+    let mut region = 0;
+    for (i, sample) in wav.reader()?.samples::<i16>().enumerate() {
+        while region < keep.len() && i >= keep[region].1 {
+            region += 1;
+        }
+        if region == keep.len() {
+            break;
+        }
+        if i >= keep[region].0 {
+            sink.write_sample(sample.map_err(|e| e.to_string())?)?;
+            kept += 1;
+        }
+    }
+
+    let rate = spec.sample_rate as f32;
+    Ok(Some(VadResult {
+        wav: sink.finish()?,
+        original_sec: total as f32 / rate,
+        trimmed_sec: kept as f32 / rate,
+        time_map: TimeMap { regions: keep, sample_rate: spec.sample_rate },
+    }))
+}
+
+/// Sample ranges to keep, after padding, short-burst removal and pause collapsing.
+/// `levels` holds one dBFS value per `frame_len` samples of a `total_len` sample recording.
+pub fn speech_regions(levels: &[f32], frame_len: usize, total_len: usize, sample_rate: u32, config: &VadConfig) -> Vec<(usize, usize)> {
+    if levels.is_empty() {
+        return Vec::new();
+    }
+
+    let threshold = speech_threshold_db(noise_floor_db(levels), config);
+    let is_speech: Vec<bool> = levels.iter().map(|&l| l >= threshold).collect();
+
+    // Group speech frames into runs, dropping bursts that are too short
+    let min_frames = (config.min_speech_ms / config.frame_ms.max(1)).max(1) as usize;
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < is_speech.len() {
+        if !is_speech[i] { i += 1; continue; }
+        let start = i;
+        while i < is_speech.len() && is_speech[i] { i += 1; }
+        if i - start >= min_frames {
+            runs.push((start * frame_len, (i * frame_len).min(total_len)));
+        }
+    }
+
+    // Pad each run and merge overlapping ones
+    let pad = sample_rate as usize * config.padding_ms as usize / 1000;
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in runs {
+        let start = start.saturating_sub(pad);
+        let end = (end + pad).min(total_len);
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    if config.max_pause_ms == 0 {
+        // Keep internal pauses: one region from first to last speech
+        return match (merged.first(), merged.last()) {
+            (Some(first), Some(last)) => vec![(first.0, last.1)],
+            _ => Vec::new(),
+        };
+    }
+
+    // Shorten gaps beyond max_pause by keeping half of the allowed pause on each side
+    let max_gap = sample_rate as usize * config.max_pause_ms as usize / 1000;
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in merged {
+        match regions.last_mut() {
+            Some(last) if start - last.1 <= max_gap => last.1 = end,
+            Some(last) => {
+                last.1 += max_gap / 2;
+                regions.push((start - (max_gap - max_gap / 2), end));
+            }
+            None => regions.push((start, end)),
+        }
+    }
+    regions
+}
+
+fn level_db(sum_sq: f64, count: usize) -> f32 {
+    let rms = (sum_sq / count.max(1) as f64).sqrt();
+    (20.0 * rms.max(1e-9).log10()) as f32
+}
+
+/// Level a frame must reach to count as speech, given the recording's noise floor.
+/// Also used by the capture quality check, so both agree on what speech is.
+pub fn speech_threshold_db(noise_floor_db: f32, config: &VadConfig) -> f32 {
+    (noise_floor_db + config.threshold_db).max(config.min_level_db)
+}
+
+/// Noise floor estimate: the 10th percentile of frame levels.
+pub fn noise_floor_db(levels: &[f32]) -> f32 {
+    let mut sorted = levels.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted[sorted.len() / 10]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+    // One 20 ms frame, the tolerance for region edges
+    const FRAME_SECS: f32 = 0.02;
+
+    /// A speech-like stand-in: a 220 Hz tone at `amplitude`.
+    fn tone(secs: f32, amplitude: f32) -> Vec<f32> {
+        let len = (RATE as f32 * secs) as usize;
+        (0..len).map(|i| amplitude * (2.0 * std::f32::consts::PI * 220.0 * i as f32 / RATE as f32).sin()).collect()
+    }
+
+    /// Deterministic white noise at `amplitude`, like a quiet room.
+    fn noise(secs: f32, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x2545_f491u32;
+        (0..(RATE as f32 * secs) as usize)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                amplitude * (state as f32 / u32::MAX as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn wav(parts: &[Vec<f32>]) -> WavPayload {
+        let spec = hound::WavSpec { channels: 1, sample_rate: RATE, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut sink = WavSink::new(spec, false).unwrap();
+        for s in parts.concat() {
+            sink.write_sample((s * i16::MAX as f32) as i16).unwrap();
+        }
+        sink.finish().unwrap()
+    }
+
+    fn assert_secs(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() <= FRAME_SECS, "{}s instead of {}s", actual, expected);
+    }
+
+    #[test]
+    fn trims_leading_and_trailing_silence() {
+        let config = VadConfig::default();
+        let input = wav(&[noise(1.0, 0.001), tone(1.0, 0.3), noise(1.0, 0.001)]);
+        let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+        let pad = config.padding_ms as f32 / 1000.0;
+        assert_secs(result.original_sec, 3.0);
+        assert_secs(result.trimmed_sec, 1.0 + 2.0 * pad);
+    }
+
+    #[test]
+    fn collapses_long_pauses_to_the_configured_gap() {
+        let config = VadConfig::default();
+        let input = wav(&[tone(0.5, 0.3), noise(3.0, 0.001), tone(0.5, 0.3)]);
+        let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+        // Both words with padding on the inside, plus the shortened pause between them
+        let pad = config.padding_ms as f32 / 1000.0;
+        let max_pause = config.max_pause_ms as f32 / 1000.0;
+        assert_secs(result.trimmed_sec, 1.0 + 2.0 * pad + max_pause);
+    }
+
+    #[test]
+    fn maps_trimmed_times_back_to_the_recording() {
+        let config = VadConfig::default();
+        let input = wav(&[noise(1.0, 0.001), tone(0.5, 0.3), noise(3.0, 0.001), tone(0.5, 0.3), noise(1.0, 0.001)]);
+        let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+        let pad = config.padding_ms as f32 / 1000.0;
+        let half_pause = config.max_pause_ms as f32 / 2000.0;
+
+        // Speech starts after the leading second of silence that was cut
+        let first = pad;
+        assert_secs(result.time_map.to_original(first), 1.0);
+        // The second word follows a pause that was shortened from 3s
+        let second = pad + 0.5 + pad + 2.0 * half_pause + pad;
+        assert_secs(result.time_map.to_original(second), 4.5);
+
+        let mut words = vec![Word { word: "zwei".into(), start: second, end: second + 0.5, confidence: None, speaker: None }];
+        result.time_map.restore_words(&mut words);
+        assert_secs(words[0].start, 4.5);
+        assert_secs(words[0].end, 5.0);
+    }
+
+    #[test]
+    fn default_time_map_keeps_times() {
+        assert_eq!(TimeMap::default().to_original(2.5), 2.5);
+    }
+
+    #[test]
+    fn keeps_pauses_when_collapsing_is_off() {
+        let config = VadConfig { max_pause_ms: 0, ..VadConfig::default() };
+        let input = wav(&[tone(0.5, 0.3), noise(3.0, 0.001), tone(0.5, 0.3)]);
+        let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+        assert_secs(result.trimmed_sec, 4.0);
+    }
+
+    #[test]
+    fn all_silence_is_none() {
+        let config = VadConfig::default();
+        assert!(trim_silence(&wav(&[noise(2.0, 0.001)]), &config).unwrap().is_none());
+        assert!(trim_silence(&wav(&[vec![0.0; RATE as usize]]), &config).unwrap().is_none());
+    }
+
+    #[test]
+    fn quiet_speech_above_the_floor_is_kept() {
+        // About -40 dBFS speech over a -70 dBFS room
+        let config = VadConfig::default();
+        let input = wav(&[noise(1.0, 0.0005), tone(1.0, 0.014), noise(1.0, 0.0005)]);
+        let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+        let pad = config.padding_ms as f32 / 1000.0;
+        assert_secs(result.trimmed_sec, 1.0 + 2.0 * pad);
+    }
+
+    #[test]
+    fn speech_under_the_noise_floor_threshold_is_kept() {
+        // Without pauses the floor estimate sits at the speech level, so no frame
+        // clears it; the recording must then be sent untrimmed rather than dropped
+        let config = VadConfig::default();
+        assert!(trim_silence(&wav(&[tone(2.0, 0.3)]), &config).unwrap().is_none());
+    }
+
+    /// Real recordings in `fixtures/vad`, each next to a `.txt` with the
+    /// hand-labelled start and end of speech in seconds (see the README there).
+    #[test]
+    #[ignore = "needs a recording in fixtures/vad"]
+    fn trims_recorded_fixtures_to_the_labelled_speech() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("vad");
+        let config = VadConfig::default();
+        let pad = config.padding_ms as f32 / 1000.0;
+        let mut checked = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("wav") {
+                continue;
+            }
+            let labels = std::fs::read_to_string(path.with_extension("txt")).unwrap();
+            let bounds: Vec<f32> = labels.split_whitespace().map(|v| v.parse().unwrap()).collect();
+            let (speech_start, speech_end) = (bounds[0], bounds[1]);
+
+            let input = WavPayload::Memory(std::fs::read(&path).unwrap());
+            let result = trim_silence(&input, &config).unwrap().expect("speech detected");
+            let start = result.time_map.to_original(0.0);
+            let end = result.time_map.to_original(result.trimmed_sec);
+            // No speech is cut, and at most the padding of room noise is kept
+            let name = path.file_name().unwrap().to_string_lossy();
+            assert!(start <= speech_start + FRAME_SECS && start >= speech_start - pad - FRAME_SECS, "{}: starts at {}s", name, start);
+            assert!(end >= speech_end - FRAME_SECS && end <= speech_end + pad + FRAME_SECS, "{}: ends at {}s", name, end);
+            checked += 1;
+        }
+        assert!(checked > 0, "no recordings in {}", dir.display());
+    }
+}
@@ -0,0 +1,238 @@
+// Custom vocabulary: names and jargon the recogniser should prefer.
+// Sent to the service with every request and used as the prompt for local Whisper.
+// Files are plain text (one term per line) or CSV (`term,boost`).
+
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use crate::error::AppError;
+
+// whisper.cpp keeps at most ~224 prompt tokens; stay well below that
+const MAX_PROMPT_CHARS: usize = 600;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VocabularyTerm {
+    pub term: String,
+    /// How strongly to favour the term; `None` is the service default (1.0).
+    #[serde(default)]
+    pub boost: Option<f32>,
+}
+
+/// Terms ordered by boost, most important first, as hint text for a prompt.
+pub fn prompt(terms: &[VocabularyTerm]) -> Option<String> {
+    let mut sorted: Vec<&VocabularyTerm> = terms.iter()
+        .filter(|t| !t.term.trim().is_empty() && !t.term.contains('\0'))
+        .collect();
+    sorted.sort_by(|a, b| b.boost.unwrap_or(1.0).total_cmp(&a.boost.unwrap_or(1.0)));
+
+    let mut prompt = String::new();
+    for t in sorted {
+        let term = t.term.trim();
+        if prompt.len() + term.len() + 2 > MAX_PROMPT_CHARS {
+            break;
+        }
+        if !prompt.is_empty() {
+            prompt.push_str(", ");
+        }
+        prompt.push_str(term);
+    }
+    (!prompt.is_empty()).then_some(prompt)
+}
+
+/// Adds `new` to `terms`; a term already present (ignoring case) takes the new boost.
+pub fn merge(terms: &mut Vec<VocabularyTerm>, new: Vec<VocabularyTerm>) {
+    for t in new {
+        // Lowercase comparison: `Müller` and `MÜLLER` are one term, `Straße` and `STRASSE` are not
+        let key = t.term.to_lowercase();
+        match terms.iter_mut().find(|e| e.term.to_lowercase() == key) {
+            Some(existing) => existing.boost = t.boost.or(existing.boost),
+            None => terms.push(t),
+        }
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// Splits CSV into records, honouring double quotes, `""` escapes and line
+/// breaks inside quotes. Each record comes with the line it starts on.
+fn csv_records(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                fields.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut fields)));
+                line += 1;
+                start = line;
+            }
+            _ => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+    records
+}
+
+fn csv_quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse(content: &str, csv: bool) -> Result<Vec<VocabularyTerm>, String> {
+    if !csv {
+        return Ok(content.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| VocabularyTerm { term: line.to_string(), boost: None })
+            .collect());
+    }
+
+    let mut terms = Vec::new();
+    let mut first = true;
+    for (line, fields) in csv_records(content) {
+        let term = fields[0].trim();
+        if term.is_empty() || term.starts_with('#') {
+            continue;
+        }
+        // Optional header row
+        if std::mem::take(&mut first) && term.eq_ignore_ascii_case("term") {
+            continue;
+        }
+        let boost = match fields.get(1).map(|b| b.trim()).filter(|b| !b.is_empty()) {
+            Some(b) => Some(b.parse::<f32>().ok().filter(|b| b.is_finite()).ok_or_else(|| format!("Line {}: invalid boost '{}'", line, b))?),
+            None => None,
+        };
+        terms.push(VocabularyTerm { term: term.to_string(), boost });
+    }
+    Ok(terms)
+}
+
+fn render(terms: &[VocabularyTerm], csv: bool) -> String {
+    let mut out = String::new();
+    if csv {
+        out.push_str("term,boost\n");
+    }
+    for t in terms {
+        if csv {
+            let boost = t.boost.map(|b| b.to_string()).unwrap_or_default();
+            out.push_str(&format!("{},{}\n", csv_quote(&t.term), boost));
+        } else {
+            out.push_str(&t.term);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Reads a `.csv` or plain text vocabulary file.
+pub fn import(path: &Path) -> Result<Vec<VocabularyTerm>, AppError> {
+    let content = std::fs::read_to_string(path)?;
+    parse(content.trim_start_matches('\u{feff}'), is_csv(path)).map_err(AppError::Other)
+}
+
+/// Writes CSV for a `.csv` path, otherwise one term per line (boosts are dropped).
+pub fn export(path: &Path, terms: &[VocabularyTerm]) -> Result<(), AppError> {
+    Ok(std::fs::write(path, render(terms, is_csv(path)))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(term: &str, boost: Option<f32>) -> VocabularyTerm {
+        VocabularyTerm { term: term.to_string(), boost }
+    }
+
+    #[test]
+    fn merge_ignores_non_ascii_case() {
+        let mut terms = vec![term("Müller", None), term("Straße", Some(1.5))];
+        merge(&mut terms, vec![term("MÜLLER", Some(2.0)), term("straße", None), term("Öl", None)]);
+        assert_eq!(terms, vec![term("Müller", Some(2.0)), term("Straße", Some(1.5)), term("Öl", None)]);
+    }
+
+    #[test]
+    fn csv_round_trips_commas_quotes_and_line_breaks() {
+        let terms = vec![
+            term("Voice2Text", Some(2.5)),
+            term("Smith, John", None),
+            term("the \"Big Apple\"", Some(0.5)),
+            term("two\nlines", Some(1.0)),
+            term("crlf\r\nline, \"quoted\"", None),
+        ];
+        let csv = render(&terms, true);
+        assert!(csv.starts_with("term,boost\n"));
+        assert_eq!(parse(&csv, true).unwrap(), terms);
+        assert_eq!(parse(&csv.replace('\n', "\r\n"), true).unwrap().len(), terms.len());
+    }
+
+    #[test]
+    fn csv_header_comments_and_blank_lines_are_skipped() {
+        let csv = "Term,Boost\n# people\n\nAda Lovelace,3\n  spaced  ,  \nGrace Hopper";
+        assert_eq!(parse(csv, true).unwrap(), vec![term("Ada Lovelace", Some(3.0)), term("spaced", None), term("Grace Hopper", None)]);
+        // Only a first row naming the column is a header
+        assert_eq!(parse("Ada,1\nterm,2", true).unwrap(), vec![term("Ada", Some(1.0)), term("term", Some(2.0))]);
+    }
+
+    #[test]
+    fn bad_boosts_name_the_line() {
+        for boost in ["high", "NaN", "inf", "1.5x"] {
+            let err = parse(&format!("term,boost\nok,1\nbad,{}", boost), true).unwrap_err();
+            assert_eq!(err, format!("Line 3: invalid boost '{}'", boost));
+        }
+        // Lines are counted in the file, across quoted line breaks
+        let err = parse("\"multi\nline\",1\nbad,x", true).unwrap_err();
+        assert_eq!(err, "Line 3: invalid boost 'x'");
+    }
+
+    #[test]
+    fn plain_text_is_one_term_per_line() {
+        let text = "# names\r\nAda Lovelace\r\n\r\n  Grace Hopper  \nx,2\n";
+        assert_eq!(parse(text, false).unwrap(), vec![term("Ada Lovelace", None), term("Grace Hopper", None), term("x,2", None)]);
+    }
+
+    #[test]
+    fn export_and_import_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let terms = vec![term("Smith, John", Some(2.0)), term("Öl", None)];
+
+        let csv = dir.path().join("terms.CSV");
+        export(&csv, &terms).unwrap();
+        assert_eq!(import(&csv).unwrap(), terms);
+
+        // Plain text keeps the terms and drops the boosts
+        let txt = dir.path().join("terms.txt");
+        export(&txt, &terms).unwrap();
+        assert_eq!(import(&txt).unwrap(), vec![term("Smith, John", None), term("Öl", None)]);
+
+        // Spreadsheet exports often start with a byte order mark
+        let bom = dir.path().join("bom.csv");
+        std::fs::write(&bom, "\u{feff}term,boost\r\nAda,1.5\r\n").unwrap();
+        assert_eq!(import(&bom).unwrap(), vec![term("Ada", Some(1.5))]);
+
+        assert!(import(&dir.path().join("missing.csv")).is_err());
+    }
+}
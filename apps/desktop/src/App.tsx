@@ -1,8 +1,33 @@
-import { useState, useEffect } from "react";
+import { useState, useEffect, Fragment } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import "./App.css";
 
+// What the user sees for each error kind the backend reports; translate here.
+// Kinds without an entry show the backend's own message.
+const ERROR_MESSAGES: Record<string, string> = {
+  DeviceNotFound: "Microphone not found - check the input device in Settings.",
+  Device: "Cannot use the microphone - check the input device in Settings.",
+  NoSignal: "Your microphone seems muted.",
+  Network: "Cannot reach the server - check your connection.",
+  Unauthorized: "Your session has expired. Reconnecting...",
+  QuotaExceeded: "Your transcription quota is used up.",
+  Banned: "This device has been blocked.",
+  ServerError: "The server ran into a problem. Please try again later.",
+  NotRegistered: "Registering... please wait.",
+  Cancelled: "Dictation cancelled.",
+};
+
+// Reported as a plain status rather than an error
+const QUIET_ERRORS = ["NoSignal", "Cancelled"];
+
+function errorMessage(err: any): string {
+  if (err && typeof err === "object" && "kind" in err) {
+    return ERROR_MESSAGES[err.kind] ?? err.message;
+  }
+  return String(err);
+}
+
 function App() {
   const [view, setView] = useState<"home" | "settings" | "history">("home");
   const [status, setStatus] = useState("Idle");
@@ -10,6 +35,9 @@ function App() {
   const [lastText, setLastText] = useState("");
   const [campaigns, setCampaigns] = useState<any[]>([]);
   const [clientStatus, setClientStatus] = useState<any>(null);
+  const [level, setLevel] = useState(0);
+  const [micWarm, setMicWarm] = useState(false);
+  const [transcribing, setTranscribing] = useState(false);
 
   useEffect(() => {
     invoke("get_version").then((v) => setVersion(v as string));
@@ -17,6 +45,7 @@ function App() {
 
     // Initial Status Fetch
     invoke("get_client_status").then(setClientStatus);
+    invoke("is_mic_warm").then((w) => setMicWarm(w as boolean));
 
     const campaignsInterval = setInterval(fetchCampaigns, 5 * 60 * 1000);
 
@@ -47,14 +76,62 @@ function App() {
       setTimeout(() => setStatus("Idle"), 3000);
     });
 
+    const unlistenPartial = listen("transcription-partial", (event) => {
+      const { text } = event.payload as { text: string; is_final: boolean };
+      if (text) setLastText(text);
+    });
+
+    const unlistenProgress = listen("transcription-progress", (event) => {
+      const { completed, total } = event.payload as { chunk: number; completed: number; total: number };
+      setStatus(`Transcribing... ${completed}/${total}`);
+    });
+
     const unlistenError = listen("transcription-error", (event) => {
-      setStatus("Error: " + event.payload);
+      const err = event.payload as { kind: string; message: string };
+      console.error("Transcription error:", err.kind, err.message);
+      setStatus(QUIET_ERRORS.includes(err.kind) ? errorMessage(err) : "Error: " + errorMessage(err));
       setTimeout(() => setStatus("Idle"), 5000);
     });
 
+    const unlistenQuality = listen("recording-quality", (event) => {
+      const quality = event.payload as any;
+      // A silent recording is reported as a NoSignal transcription error
+      if (quality.diagnosis === "clipping") {
+        setStatus("Input is clipping - lower the mic gain.");
+        setTimeout(() => setStatus("Idle"), 5000);
+      }
+    });
+
     const unlistenState = listen("recording-state", (event) => {
       const isRecording = event.payload as boolean;
       setStatus(isRecording ? "Recording..." : "Idle");
+      if (!isRecording) setLevel(0);
+    });
+
+    const unlistenDevice = listen("recording-error", (event) => {
+      const { message, recovered } = event.payload as { message: string; recovered: boolean };
+      if (recovered) {
+        setStatus("Microphone lost - recording on default device...");
+        setTimeout(() => setStatus((s) => (s.startsWith("Microphone lost") ? "Recording..." : s)), 3000);
+      } else {
+        setStatus("Microphone lost: " + message);
+        setTimeout(() => setStatus("Idle"), 5000);
+      }
+    });
+
+    const unlistenWarm = listen("mic-warm", (event) => {
+      setMicWarm(event.payload as boolean);
+    });
+
+    const unlistenTranscribing = listen("transcribing", (event) => {
+      setTranscribing(event.payload as boolean);
+    });
+
+    const unlistenLevel = listen("audio-level", (event) => {
+      const { rms } = event.payload as { rms: number; peak: number };
+      // Map -60..0 dBFS onto 0..1 for the meter bar
+      const db = 20 * Math.log10(Math.max(rms, 1e-6));
+      setLevel(Math.min(1, Math.max(0, (db + 60) / 60)));
     });
 
     const unlistenMessages = listen("new-messages", (event) => {
@@ -73,7 +150,14 @@ function App() {
       clearInterval(statusInterval);
       unlistenResult.then((f) => f());
       unlistenError.then((f) => f());
+      unlistenPartial.then((f) => f());
+      unlistenProgress.then((f) => f());
+      unlistenQuality.then((f) => f());
       unlistenState.then((f) => f());
+      unlistenLevel.then((f) => f());
+      unlistenWarm.then((f) => f());
+      unlistenTranscribing.then((f) => f());
+      unlistenDevice.then((f) => f());
       unlistenMessages.then((f) => f());
     };
   }, []);
@@ -94,7 +178,7 @@ function App() {
       const result = await invoke("toggle_recording");
       setStatus(result === "started" ? "Recording..." : "Idle");
     } catch (err) {
-      setStatus("Error: " + err);
+      setStatus("Error: " + errorMessage(err));
     }
   };
 
@@ -137,6 +221,12 @@ function App() {
         </svg>
       </div>
 
+      {status === "Recording..." && (
+        <div style={{ width: "160px", height: "6px", background: "rgba(255,255,255,0.1)", borderRadius: "3px", overflow: "hidden", marginBottom: "12px" }}>
+          <div style={{ width: `${level * 100}%`, height: "100%", background: level > 0.95 ? "#ef4444" : "#818cf8", transition: "width 50ms linear" }} />
+        </div>
+      )}
+
       <div className="status-text" style={{ fontSize: "18px", fontWeight: 600, marginBottom: "10px" }}>{status}</div>
 
       {(lastText || status.includes("Copied") || status === "No speech detected.") && (
@@ -145,10 +235,26 @@ function App() {
         </div>
       )}
 
+      {(status === "Recording..." || transcribing) && (
+        <button
+          className="btn-secondary"
+          style={{ padding: "6px 12px", fontSize: "12px", marginBottom: "10px" }}
+          onClick={() => invoke("cancel_transcription", {}).catch((err) => alert(errorMessage(err)))}
+        >
+          Cancel (Esc)
+        </button>
+      )}
+
       <div className="shortcut-hint">
-        <b>F8</b> or <b>Ctrl+F12</b> to toggle
+        <b>F8</b> or <b>Ctrl+F12</b> to toggle, <b>Esc</b> to cancel
       </div>
 
+      {micWarm && (
+        <div style={{ fontSize: "11px", color: "#fbbf24", marginTop: "6px" }} title="The microphone stays open to keep a short pre-roll buffer in memory">
+          ● Warm mic active
+        </div>
+      )}
+
       <div className="campaign-container">
         <div className="campaign-header">Latest News</div>
         {campaigns.length === 0 ? (
@@ -188,18 +294,73 @@ function HistoryButton({ onClick }: { onClick: () => void }) {
 
 function HistoryView({ onBack }: { onBack: () => void }) {
   const [items, setItems] = useState<any[]>([]);
+  const [pending, setPending] = useState<any[]>([]);
+  const [retrying, setRetrying] = useState<string | null>(null);
   const [search, setSearch] = useState("");
+  const [audioPath, setAudioPath] = useState("");
+  const [subtitlePath, setSubtitlePath] = useState("");
+  const [subtitling, setSubtitling] = useState(false);
 
   useEffect(() => {
     loadHistory();
   }, [search]);
 
+  useEffect(() => {
+    loadOutbox();
+    const unlisten = listen("outbox-changed", () => {
+      loadOutbox();
+      loadHistory();
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const loadOutbox = () => {
+    invoke("list_outbox")
+      .then((res: any) => setPending(res))
+      .catch(console.error);
+  };
+
+  const retryPending = (id: string) => {
+    setRetrying(id);
+    invoke("retry_outbox", { id })
+      .catch((err) => alert("Retry failed: " + errorMessage(err)))
+      .finally(() => setRetrying(null));
+  };
+
+  const discardPending = (id: string) => {
+    if (confirm("Discard this recording? It cannot be recovered.")) {
+      invoke("discard_outbox", { id }).catch((err) => alert(errorMessage(err)));
+    }
+  };
+
   const loadHistory = () => {
     invoke("get_history", { limit: 50, offset: 0, search: search || null })
       .then((res: any) => setItems(res))
       .catch(console.error);
   };
 
+  const exportSubtitles = (id: string) => {
+    const path = prompt("Save subtitles to (.srt or .vtt):");
+    if (path) {
+      invoke("export_subtitles", { id, path })
+        .then((cues) => alert(`Wrote ${cues} subtitles to ${path}`))
+        .catch((err) => alert("Export failed: " + errorMessage(err)));
+    }
+  };
+
+  const subtitleFile = () => {
+    setSubtitling(true);
+    invoke("transcribe_file_subtitles", { input: audioPath, output: subtitlePath })
+      .then((cues) => {
+        alert(`Wrote ${cues} subtitles to ${subtitlePath}`);
+        loadHistory();
+      })
+      .catch((err) => alert("Subtitles failed: " + errorMessage(err)))
+      .finally(() => setSubtitling(false));
+  };
+
   const clearHistory = () => {
     if (confirm("Clear all history?")) {
       invoke("clear_all_history").then(() => setItems([]));
@@ -223,12 +384,75 @@ function HistoryView({ onBack }: { onBack: () => void }) {
         style={{ width: "100%", background: "#18181b", color: "white", border: "1px solid rgba(255,255,255,0.1)", padding: "12px", borderRadius: "8px", marginBottom: "15px" }}
       />
 
+      <div className="settings-card" style={{ padding: "12px", textAlign: "left", marginBottom: "15px" }}>
+        <div className="card-label">Subtitles from an audio file</div>
+        <input
+          type="text"
+          placeholder="Audio file (.wav or .flac)"
+          value={audioPath}
+          onChange={(e) => setAudioPath(e.target.value)}
+          style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+        />
+        <input
+          type="text"
+          placeholder="Subtitle file (.srt or .vtt)"
+          value={subtitlePath}
+          onChange={(e) => setSubtitlePath(e.target.value)}
+          style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+        />
+        <button
+          className="btn-secondary"
+          style={{ width: "100%", marginTop: "8px" }}
+          disabled={!audioPath || !subtitlePath || subtitling}
+          onClick={subtitleFile}
+        >
+          {subtitling ? "Transcribing..." : "Create Subtitles"}
+        </button>
+      </div>
+
       <div style={{ flex: 1, overflowY: "auto", display: "flex", flexDirection: "column", gap: "10px" }}>
+        {pending.map((entry) => (
+          <div key={entry.id} className="settings-card" style={{ padding: "12px", textAlign: "left", borderColor: "rgba(239,68,68,0.4)" }}>
+            <div style={{ fontSize: "10px", color: "#9ca3af", marginBottom: "5px" }}>
+              {new Date(entry.timestamp).toLocaleString()}
+              {entry.cancelled ? (
+                <span style={{ marginLeft: "8px", color: "#9ca3af" }}>cancelled ({entry.duration.toFixed(1)}s)</span>
+              ) : (
+                <span style={{ marginLeft: "8px", color: "#ef4444" }}>not transcribed ({entry.duration.toFixed(1)}s, {entry.attempts} attempts)</span>
+              )}
+            </div>
+            <div style={{ fontSize: "12px", color: "#fca5a5", marginBottom: "10px", wordBreak: "break-word" }}>{entry.error}</div>
+            <div style={{ display: "flex", gap: "8px" }}>
+              <button
+                className="btn-secondary"
+                style={{ padding: "4px 8px", fontSize: "10px" }}
+                disabled={retrying === entry.id}
+                onClick={() => retryPending(entry.id)}
+              >
+                {retrying === entry.id ? "RETRYING..." : "RETRY"}
+              </button>
+              <button
+                className="btn-secondary"
+                style={{ padding: "4px 8px", fontSize: "10px", color: "#ef4444" }}
+                disabled={retrying === entry.id}
+                onClick={() => discardPending(entry.id)}
+              >
+                DISCARD
+              </button>
+            </div>
+          </div>
+        ))}
         {items.length === 0 && <div style={{ opacity: 0.4, textAlign: "center", marginTop: "20px" }}>No history found</div>}
         {items.map((item) => (
           <div key={item.id} className="settings-card" style={{ padding: "12px", textAlign: "left" }}>
-            <div style={{ fontSize: "10px", color: "#9ca3af", marginBottom: "5px" }}>{new Date(item.timestamp).toLocaleString()}</div>
-            <div style={{ fontSize: "13px", color: "#e5e7eb", marginBottom: "10px", wordBreak: "break-word" }}>{item.text}</div>
+            <div style={{ fontSize: "10px", color: "#9ca3af", marginBottom: "5px" }}>
+              {new Date(item.timestamp).toLocaleString()}
+              {item.backend && <span style={{ marginLeft: "8px", opacity: 0.7 }}>via {item.backend}</span>}
+              {item.language && <span style={{ marginLeft: "8px", opacity: 0.7, textTransform: "uppercase" }}>{item.language}</span>}
+            </div>
+            <div style={{ fontSize: "13px", color: "#e5e7eb", marginBottom: "10px", wordBreak: "break-word" }}>
+              {item.words ? <TimedText words={item.words} /> : item.text}
+            </div>
             <button
               className="btn-secondary"
               style={{ padding: "4px 8px", fontSize: "10px" }}
@@ -236,6 +460,15 @@ function HistoryView({ onBack }: { onBack: () => void }) {
             >
               COPY
             </button>
+            {item.words && (
+              <button
+                className="btn-secondary"
+                style={{ padding: "4px 8px", fontSize: "10px", marginLeft: "6px" }}
+                onClick={() => exportSubtitles(item.id)}
+              >
+                SUBTITLES
+              </button>
+            )}
           </div>
         ))}
       </div>
@@ -247,21 +480,62 @@ function HistoryView({ onBack }: { onBack: () => void }) {
   );
 }
 
+// Words recognised with less confidence than this are highlighted for review
+const LOW_CONFIDENCE = 0.6;
+
+function TimedText({ words }: { words: any[] }) {
+  return (
+    <>
+      {words.map((w, i) => {
+        const low = w.confidence != null && w.confidence < LOW_CONFIDENCE;
+        return (
+          <Fragment key={i}>
+            {i > 0 && " "}
+            <span
+              title={`${w.start.toFixed(1)}s${w.confidence != null ? ` · ${Math.round(w.confidence * 100)}%` : ""}${w.speaker ? ` · ${w.speaker}` : ""}`}
+              style={low ? { background: "rgba(234,179,8,0.25)", borderRadius: "3px" } : undefined}
+            >
+              {w.word}
+            </span>
+          </Fragment>
+        );
+      })}
+    </>
+  );
+}
+
+interface InputDevice {
+  id: string;
+  name: string;
+  host_api: string;
+  is_default: boolean;
+  sample_rates: number[];
+  channels: number[];
+}
+
 function SettingsView({ onBack, version, clientStatus }: { onBack: () => void; version: string; clientStatus: any }) {
   const [hwId, setHwId] = useState("");
-  const [devices, setDevices] = useState<string[]>([]);
+  const [devices, setDevices] = useState<InputDevice[]>([]);
   const [selectedDevice, setSelectedDevice] = useState("Default");
+  const [settings, setSettings] = useState<any>(null);
+  const [networkError, setNetworkError] = useState("");
+  const [vocabPath, setVocabPath] = useState("");
+  const [vocabMessage, setVocabMessage] = useState("");
+  const [apiKey, setApiKey] = useState("");
+  const [localWhisper, setLocalWhisper] = useState(false);
 
   useEffect(() => {
     // Initial Fetch
     invoke("get_hw_id").then((id: any) => setHwId(id as string));
+    invoke("get_settings").then(setSettings);
+    invoke("has_local_whisper").then((available) => setLocalWhisper(available as boolean));
 
     // Fetch Devices & Active Selection
     Promise.all([
       invoke("get_input_devices"),
       invoke("get_input_device")
     ]).then(([d, active]) => {
-      setDevices(["Default", ...(d as string[])]);
+      setDevices(d as InputDevice[]);
       if (active) {
         setSelectedDevice(active as string);
       }
@@ -271,7 +545,50 @@ function SettingsView({ onBack, version, clientStatus }: { onBack: () => void; v
   const handleDeviceChange = (e: React.ChangeEvent<HTMLSelectElement>) => {
     const dev = e.target.value;
     setSelectedDevice(dev);
-    invoke("set_input_device", { name: dev });
+    invoke("set_input_device", { id: dev });
+    setSettings((prev: any) => prev && { ...prev, input_device: dev === "Default" ? null : dev });
+  };
+
+  // The key goes to the OS credential store; settings only say whether one is set
+  const saveApiKey = (key: string) => {
+    invoke("set_api_key", { key })
+      .then((isSet) => {
+        setApiKey("");
+        setSettings((s: any) => ({ ...s, transcription: { ...s.transcription, openai: { ...s.transcription.openai, api_key_set: isSet } } }));
+      })
+      .catch((err) => alert("Could not save the API key: " + errorMessage(err)));
+  };
+
+  const updateSettings = (next: any) => {
+    setSettings(next);
+    invoke("update_settings", { newSettings: next }).catch(e => console.error("Settings Save Error", e));
+  };
+
+  // Network changes are validated by the backend (proxy URL, certificate files)
+  const updateNetwork = (api: any) => {
+    const next = { ...settings, api };
+    setSettings(next);
+    invoke("update_settings", { newSettings: next })
+      .then(() => setNetworkError(""))
+      .catch((e) => setNetworkError(errorMessage(e)));
+  };
+
+  const setVocabulary = (vocabulary: any[]) =>
+    updateSettings({ ...settings, transcription: { ...settings.transcription, vocabulary } });
+
+  const importVocabulary = () => {
+    invoke("import_vocabulary", { path: vocabPath })
+      .then((vocabulary: any) => {
+        setSettings({ ...settings, transcription: { ...settings.transcription, vocabulary } });
+        setVocabMessage(`Vocabulary now has ${vocabulary.length} terms.`);
+      })
+      .catch((e) => setVocabMessage("Import failed: " + errorMessage(e)));
+  };
+
+  const exportVocabulary = () => {
+    invoke("export_vocabulary", { path: vocabPath })
+      .then(() => setVocabMessage("Exported to " + vocabPath))
+      .catch((e) => setVocabMessage("Export failed: " + errorMessage(e)));
   };
 
   const openLogs = () => invoke("open_data_folder");
@@ -333,12 +650,303 @@ function SettingsView({ onBack, version, clientStatus }: { onBack: () => void; v
           onChange={handleDeviceChange}
           style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "5px" }}
         >
-          {devices.map((d, i) => (
-            <option key={i} value={d}>{d}</option>
-          ))}
+          <option value="Default">Default</option>
+          {devices.map((d, i) => {
+            // Number identical devices (e.g. two of the same headset) so they can be told apart
+            const twins = devices.filter((o) => o.name === d.name);
+            const suffix = twins.length > 1 ? ` #${devices.slice(0, i + 1).filter((o) => o.name === d.name).length}` : "";
+            return (
+              <option key={d.id} value={d.id}>
+                {d.name}{suffix}{d.is_default ? " (System Default)" : ""}
+              </option>
+            );
+          })}
         </select>
       </div>
 
+      {settings && (
+        <div className="settings-card">
+          <div className="card-label">Warm Mic (Pre-Roll)</div>
+          <label style={{ display: "flex", alignItems: "center", gap: "8px", fontSize: "13px" }}>
+            <input
+              type="checkbox"
+              checked={settings.warm_mic.enabled}
+              onChange={(e) => updateSettings({ ...settings, warm_mic: { ...settings.warm_mic, enabled: e.target.checked } })}
+            />
+            Keep the microphone open so the first word is never lost
+          </label>
+          <select
+            value={settings.warm_mic.preroll_ms}
+            disabled={!settings.warm_mic.enabled}
+            onChange={(e) => updateSettings({ ...settings, warm_mic: { ...settings.warm_mic, preroll_ms: Number(e.target.value) } })}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          >
+            {[300, 500, 750, 1000].map((ms) => (
+              <option key={ms} value={ms}>{ms} ms pre-roll</option>
+            ))}
+          </select>
+          <div style={{ fontSize: "11px", opacity: 0.5, marginTop: "6px" }}>
+            Audio is only kept in memory and is discarded unless you start a recording.
+          </div>
+        </div>
+      )}
+
+      {settings && (
+        <div className="settings-card">
+          <div className="card-label">Cancelling</div>
+          <label style={{ display: "flex", alignItems: "center", gap: "8px", fontSize: "13px" }}>
+            <input
+              type="checkbox"
+              checked={settings.keep_cancelled}
+              onChange={(e) => updateSettings({ ...settings, keep_cancelled: e.target.checked })}
+            />
+            Keep the audio of cancelled dictations in History
+          </label>
+          <div style={{ fontSize: "11px", opacity: 0.5, marginTop: "6px" }}>
+            Press Esc while recording, or within a few seconds after, to cancel; nothing is typed.
+          </div>
+        </div>
+      )}
+
+      {settings && (
+        <div className="settings-card">
+          <div className="card-label">Transcription Service</div>
+          <select
+            value={settings.transcription.backend}
+            onChange={(e) => updateSettings({ ...settings, transcription: { ...settings.transcription, backend: e.target.value } })}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          >
+            <option value="proxy">Voice2Text Cloud</option>
+            <option value="openai">OpenAI-compatible server</option>
+            {(localWhisper || settings.transcription.backend === "local") && (
+              <option value="local">Offline (local Whisper model)</option>
+            )}
+          </select>
+          <select
+            value={settings.transcription.language.language}
+            onChange={(e) => updateSettings({
+              ...settings,
+              transcription: { ...settings.transcription, language: { ...settings.transcription.language, language: e.target.value } },
+            })}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          >
+            {[
+              ["", "Detect language automatically"],
+              ["de", "German"],
+              ["en", "English"],
+              ["fr", "French"],
+              ["es", "Spanish"],
+              ["it", "Italian"],
+              ["nl", "Dutch"],
+              ["pl", "Polish"],
+              ["pt", "Portuguese"],
+            ].map(([code, label]) => (
+              <option key={code} value={code}>{label}</option>
+            ))}
+          </select>
+          {!settings.transcription.language.language && (
+            <input
+              type="text"
+              placeholder="Only detect these languages (e.g. de, en)"
+              value={settings.transcription.language.allowed.join(",")}
+              onChange={(e) => updateSettings({
+                ...settings,
+                transcription: {
+                  ...settings.transcription,
+                  language: { ...settings.transcription.language, allowed: e.target.value.split(",") },
+                },
+              })}
+              style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+            />
+          )}
+          {settings.transcription.backend === "openai" && (
+            <>
+              {[
+                ["base_url", "Server URL (e.g. https://api.openai.com)"],
+                ["model", "Model (e.g. whisper-1)"],
+              ].map(([key, placeholder]) => (
+                <input
+                  key={key}
+                  type="text"
+                  placeholder={placeholder}
+                  value={settings.transcription.openai[key]}
+                  onChange={(e) => updateSettings({
+                    ...settings,
+                    transcription: { ...settings.transcription, openai: { ...settings.transcription.openai, [key]: e.target.value } },
+                  })}
+                  style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+                />
+              ))}
+              <div style={{ display: "flex", gap: "8px", marginTop: "8px" }}>
+                <input
+                  type="password"
+                  placeholder={settings.transcription.openai.api_key_set ? "API key stored - enter a new one to replace it" : "API key"}
+                  value={apiKey}
+                  onChange={(e) => setApiKey(e.target.value)}
+                  style={{ flex: 1, background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)" }}
+                />
+                <button className="btn-secondary" style={{ padding: "4px 10px", fontSize: "11px" }} disabled={!apiKey.trim()} onClick={() => saveApiKey(apiKey)}>
+                  SAVE
+                </button>
+                {settings.transcription.openai.api_key_set && (
+                  <button className="btn-secondary" style={{ padding: "4px 10px", fontSize: "11px" }} onClick={() => saveApiKey("")}>
+                    REMOVE
+                  </button>
+                )}
+              </div>
+            </>
+          )}
+          {localWhisper && (
+            <>
+              <input
+                type="text"
+                placeholder="Local Whisper model file (ggml/gguf)"
+                value={settings.transcription.local.model_path}
+                onChange={(e) => updateSettings({
+                  ...settings,
+                  transcription: { ...settings.transcription, local: { ...settings.transcription.local, model_path: e.target.value } },
+                })}
+                style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+              />
+              <select
+                value={settings.transcription.local.threads}
+                onChange={(e) => updateSettings({
+                  ...settings,
+                  transcription: { ...settings.transcription, local: { ...settings.transcription.local, threads: Number(e.target.value) } },
+                })}
+                style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+              >
+                {[0, 1, 2, 4, 6, 8, 12, 16].map((n) => (
+                  <option key={n} value={n}>{n === 0 ? "Automatic CPU threads" : `${n} CPU threads`}</option>
+                ))}
+              </select>
+              {settings.transcription.backend !== "local" && (
+                <label style={{ display: "flex", alignItems: "center", gap: "8px", fontSize: "13px", marginTop: "8px" }}>
+                  <input
+                    type="checkbox"
+                    checked={settings.transcription.local.fallback}
+                    disabled={!settings.transcription.local.model_path}
+                    onChange={(e) => updateSettings({
+                      ...settings,
+                      transcription: { ...settings.transcription, local: { ...settings.transcription.local, fallback: e.target.checked } },
+                    })}
+                  />
+                  Transcribe offline when the service is unreachable
+                </label>
+              )}
+            </>
+          )}
+          <label style={{ display: "flex", alignItems: "center", gap: "8px", fontSize: "13px", marginTop: "8px" }}>
+            <input
+              type="checkbox"
+              checked={settings.transcription.streaming.enabled}
+              onChange={(e) => updateSettings({
+                ...settings,
+                transcription: { ...settings.transcription, streaming: { ...settings.transcription.streaming, enabled: e.target.checked } },
+              })}
+            />
+            Stream while speaking (live preview)
+          </label>
+          {settings.transcription.streaming.enabled && (
+            <input
+              type="text"
+              placeholder="Streaming server (leave empty for Voice2Text Cloud)"
+              value={settings.transcription.streaming.url}
+              onChange={(e) => updateSettings({
+                ...settings,
+                transcription: { ...settings.transcription, streaming: { ...settings.transcription.streaming, url: e.target.value } },
+              })}
+              style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+            />
+          )}
+        </div>
+      )}
+
+      {settings && (
+        <div className="settings-card">
+          <div className="card-label">Vocabulary</div>
+          <div style={{ fontSize: "11px", opacity: 0.6, marginTop: "4px" }}>
+            Names and terms that should be recognised. A higher boost favours a term more strongly.
+          </div>
+          {settings.transcription.vocabulary.map((t: any, i: number) => (
+            <div key={i} style={{ display: "flex", gap: "6px", marginTop: "6px" }}>
+              <input
+                type="text"
+                placeholder="Term"
+                value={t.term}
+                onChange={(e) => setVocabulary(settings.transcription.vocabulary.map((v: any, j: number) => (j === i ? { ...v, term: e.target.value } : v)))}
+                style={{ flex: 1, background: "#000", color: "white", padding: "6px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)" }}
+              />
+              <input
+                type="number"
+                step="0.5"
+                min="0"
+                placeholder="Boost"
+                value={t.boost ?? ""}
+                onChange={(e) => setVocabulary(settings.transcription.vocabulary.map((v: any, j: number) => (
+                  j === i ? { ...v, boost: e.target.value === "" ? null : Number(e.target.value) } : v
+                )))}
+                style={{ width: "70px", background: "#000", color: "white", padding: "6px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)" }}
+              />
+              <button
+                className="btn-secondary"
+                style={{ padding: "4px 8px", fontSize: "10px" }}
+                onClick={() => setVocabulary(settings.transcription.vocabulary.filter((_: any, j: number) => j !== i))}
+              >
+                REMOVE
+              </button>
+            </div>
+          ))}
+          <button
+            className="btn-secondary"
+            style={{ padding: "4px 8px", fontSize: "11px", marginTop: "8px" }}
+            onClick={() => setVocabulary([...settings.transcription.vocabulary, { term: "", boost: null }])}
+          >
+            Add term
+          </button>
+          <input
+            type="text"
+            placeholder="Vocabulary file (.csv with term,boost or .txt with one term per line)"
+            value={vocabPath}
+            onChange={(e) => setVocabPath(e.target.value)}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          />
+          <div style={{ display: "flex", gap: "8px", marginTop: "8px" }}>
+            <button className="btn-secondary" style={{ flex: 1 }} disabled={!vocabPath} onClick={importVocabulary}>Import</button>
+            <button className="btn-secondary" style={{ flex: 1 }} disabled={!vocabPath} onClick={exportVocabulary}>Export</button>
+          </div>
+          {vocabMessage && <div style={{ fontSize: "11px", opacity: 0.7, marginTop: "6px" }}>{vocabMessage}</div>}
+        </div>
+      )}
+
+      {settings && (
+        <div className="settings-card">
+          <div className="card-label">Network</div>
+          <input
+            type="text"
+            placeholder="Service URL (leave empty for Voice2Text Cloud)"
+            value={settings.api.base_url}
+            onChange={(e) => updateNetwork({ ...settings.api, base_url: e.target.value })}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          />
+          <input
+            type="text"
+            placeholder="Proxy (e.g. http://proxy.corp:8080, empty uses system settings)"
+            value={settings.api.proxy}
+            onChange={(e) => updateNetwork({ ...settings.api, proxy: e.target.value })}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px" }}
+          />
+          <textarea
+            placeholder="Extra CA certificate files (.pem), one per line"
+            value={settings.api.ca_certs.join("\n")}
+            onChange={(e) => updateNetwork({ ...settings.api, ca_certs: e.target.value.split("\n") })}
+            rows={2}
+            style={{ width: "100%", background: "#000", color: "white", padding: "8px", borderRadius: "6px", border: "1px solid rgba(255,255,255,0.2)", marginTop: "8px", resize: "vertical" }}
+          />
+          {networkError && <div style={{ fontSize: "11px", color: "#ef4444", marginTop: "6px" }}>{networkError}</div>}
+        </div>
+      )}
+
       <div style={{ marginTop: "12px", display: "flex", flexDirection: "column", gap: "12px" }}>
         <button className="btn-primary" onClick={manageSubscription}>
           Manage Subscription